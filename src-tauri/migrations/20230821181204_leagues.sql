@@ -0,0 +1,13 @@
+CREATE TABLE leagues (
+    id TEXT PRIMARY KEY NOT NULL,
+    realm TEXT,
+    start_at TEXT,
+    end_at TEXT,
+    ninja_indexed BOOLEAN NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL
+);
+
+-- Seed the leagues that used to be hard-coded so pricing keeps working until the first refresh
+INSERT INTO leagues (id, realm, ninja_indexed, updated_at) VALUES
+    ("Standard", "pc", 1, datetime('now')),
+    ("Ancestor", "pc", 1, datetime('now'));
@@ -0,0 +1,2 @@
+ALTER TABLE price ADD COLUMN provider TEXT NOT NULL DEFAULT "poe.ninja";
+ALTER TABLE profiles ADD COLUMN price_provider TEXT NOT NULL DEFAULT "poe.ninja";
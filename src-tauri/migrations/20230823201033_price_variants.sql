@@ -0,0 +1,9 @@
+ALTER TABLE price ADD COLUMN category TEXT;
+ALTER TABLE price ADD COLUMN base_type TEXT;
+ALTER TABLE price ADD COLUMN variant TEXT;
+ALTER TABLE price ADD COLUMN links INTEGER;
+ALTER TABLE price ADD COLUMN gem_level INTEGER;
+ALTER TABLE price ADD COLUMN gem_quality INTEGER;
+ALTER TABLE price ADD COLUMN corrupted BOOLEAN;
+ALTER TABLE price ADD COLUMN map_tier INTEGER;
+ALTER TABLE price ADD COLUMN item_level INTEGER;
@@ -0,0 +1 @@
+ALTER TABLE price ADD COLUMN gem_quality_type TEXT;
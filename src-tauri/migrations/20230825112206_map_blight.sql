@@ -0,0 +1 @@
+ALTER TABLE price ADD COLUMN blight TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE price ADD COLUMN influence TEXT;
+ALTER TABLE price ADD COLUMN passives INTEGER;
@@ -0,0 +1,3 @@
+ALTER TABLE price ADD COLUMN sparkline TEXT;
+ALTER TABLE price ADD COLUMN total_change REAL;
+ALTER TABLE item ADD COLUMN price_change REAL;
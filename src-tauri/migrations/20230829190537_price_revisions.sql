@@ -0,0 +1,81 @@
+CREATE TABLE price_revisions (
+    id INTEGER PRIMARY KEY,
+    league TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+) STRICT;
+
+-- Revisions used to be numbered per league, give every (league, provider, revision) its own id
+CREATE TEMPORARY TABLE revision_map (
+    id INTEGER PRIMARY KEY,
+    league TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    old_revision INTEGER NOT NULL,
+    timestamp TEXT NOT NULL
+);
+
+INSERT INTO revision_map (league, provider, old_revision, timestamp)
+    SELECT league, provider, revision, MIN(timestamp) FROM price
+    GROUP BY league, provider, revision
+    ORDER BY MIN(timestamp);
+
+-- Snapshots that were pinned to a revision their pricing league never had get an empty revision
+INSERT INTO revision_map (league, provider, old_revision, timestamp)
+    SELECT p.pricing_league, p.price_provider, s.pricing_revision, MIN(s.timestamp)
+    FROM snapshots s JOIN profiles p ON p.id = s.profile_id
+    WHERE NOT EXISTS (
+        SELECT 1 FROM revision_map m
+        WHERE m.league = p.pricing_league AND m.provider = p.price_provider AND m.old_revision = s.pricing_revision
+    )
+    GROUP BY p.pricing_league, p.price_provider, s.pricing_revision;
+
+INSERT INTO price_revisions (id, league, provider, timestamp)
+    SELECT id, league, provider, timestamp FROM revision_map;
+
+UPDATE price SET revision = (
+    SELECT m.id FROM revision_map m
+    WHERE m.league = price.league AND m.provider = price.provider AND m.old_revision = price.revision
+);
+
+-- Rebuild snapshots with a foreign key on its revision. item is rebuilt alongside it so that
+-- dropping the old snapshots table never leaves item pointing at missing rows.
+CREATE TABLE snapshots_new (
+    id INTEGER PRIMARY KEY,
+    profile_id INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    pricing_revision INTEGER NOT NULL,
+    value REAL NOT NULL,
+
+    FOREIGN KEY(profile_id) REFERENCES profiles(id),
+    FOREIGN KEY(pricing_revision) REFERENCES price_revisions(id)
+) STRICT;
+
+INSERT INTO snapshots_new (id, profile_id, timestamp, pricing_revision, value)
+    SELECT s.id, s.profile_id, s.timestamp, m.id, s.value
+    FROM snapshots s
+    JOIN profiles p ON p.id = s.profile_id
+    JOIN revision_map m
+        ON m.league = p.pricing_league AND m.provider = p.price_provider AND m.old_revision = s.pricing_revision;
+
+CREATE TABLE item_new (
+    id INTEGER PRIMARY KEY,
+    snapshot_id INTEGER NOT NULL,
+    stash_id TEXT NOT NULL,
+    data TEXT NOT NULL,
+    value REAL NOT NULL,
+    price_change REAL,
+
+    FOREIGN KEY(stash_id) REFERENCES stashes(id),
+    FOREIGN KEY(snapshot_id) REFERENCES snapshots_new(id)
+) STRICT;
+
+INSERT INTO item_new (id, snapshot_id, stash_id, data, value, price_change)
+    SELECT id, snapshot_id, stash_id, data, value, price_change FROM item
+    WHERE snapshot_id IN (SELECT id FROM snapshots_new);
+
+DROP TABLE item;
+DROP TABLE snapshots;
+ALTER TABLE snapshots_new RENAME TO snapshots;
+ALTER TABLE item_new RENAME TO item;
+
+DROP TABLE revision_map;
@@ -0,0 +1,10 @@
+CREATE TABLE valuations (
+    id INTEGER PRIMARY KEY,
+    snapshot_id INTEGER NOT NULL,
+    pricing_revision INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    value REAL NOT NULL,
+
+    FOREIGN KEY(snapshot_id) REFERENCES snapshots(id),
+    FOREIGN KEY(pricing_revision) REFERENCES price_revisions(id)
+) STRICT;
@@ -0,0 +1,3 @@
+ALTER TABLE price ADD COLUMN pay_price REAL;
+ALTER TABLE profiles ADD COLUMN valuation_mode TEXT NOT NULL DEFAULT "receive";
+ALTER TABLE valuations ADD COLUMN valuation_mode TEXT NOT NULL DEFAULT "receive";
@@ -0,0 +1,6 @@
+ALTER TABLE price ADD COLUMN listing_count INTEGER;
+ALTER TABLE profiles ADD COLUMN min_listing_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE profiles ADD COLUMN exclude_low_confidence BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE item ADD COLUMN low_confidence INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE snapshots ADD COLUMN low_confidence_value REAL NOT NULL DEFAULT 0;
+ALTER TABLE valuations ADD COLUMN low_confidence_value REAL NOT NULL DEFAULT 0;
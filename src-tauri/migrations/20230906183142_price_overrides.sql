@@ -0,0 +1,13 @@
+CREATE TABLE price_overrides (
+    id INTEGER PRIMARY KEY,
+    profile_id INTEGER,
+    name TEXT NOT NULL,
+    base_type TEXT,
+    variant TEXT,
+    price REAL NOT NULL,
+    timestamp TEXT NOT NULL,
+
+    FOREIGN KEY(profile_id) REFERENCES profiles(id)
+) STRICT;
+
+CREATE INDEX price_overrides_name ON price_overrides(name COLLATE NOCASE);
@@ -0,0 +1,3 @@
+ALTER TABLE item ADD COLUMN listed_value REAL;
+ALTER TABLE snapshots ADD COLUMN listed_value REAL NOT NULL DEFAULT 0;
+ALTER TABLE valuations ADD COLUMN listed_value REAL NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+CREATE TABLE settings (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    price_refresh_minutes INTEGER NOT NULL DEFAULT 60
+) STRICT;
+
+INSERT INTO settings (id) VALUES (1);
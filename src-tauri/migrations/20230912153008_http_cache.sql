@@ -0,0 +1,11 @@
+CREATE TABLE http_cache (
+    league TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    category TEXT NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+
+    PRIMARY KEY (league, provider, category)
+) STRICT;
+
+ALTER TABLE settings ADD COLUMN http_timeout_seconds INTEGER NOT NULL DEFAULT 30;
@@ -0,0 +1 @@
+ALTER TABLE price_revisions ADD COLUMN source TEXT;
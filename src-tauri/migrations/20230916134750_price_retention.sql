@@ -0,0 +1,2 @@
+ALTER TABLE settings ADD COLUMN price_retention_days INTEGER NOT NULL DEFAULT 7;
+ALTER TABLE settings ADD COLUMN prune_prices_automatically INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+CREATE INDEX price_league_revision_name ON price(league, revision, name);
+CREATE INDEX price_revision_category ON price(revision, category);
+CREATE INDEX item_snapshot ON item(snapshot_id);
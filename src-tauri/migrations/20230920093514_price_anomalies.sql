@@ -0,0 +1,21 @@
+CREATE TABLE price_anomalies (
+    id INTEGER PRIMARY KEY,
+    revision INTEGER NOT NULL,
+    previous_revision INTEGER NOT NULL,
+    price_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    category TEXT,
+    variant TEXT,
+    price REAL NOT NULL,
+    previous_price REAL NOT NULL,
+    previous_pay_price REAL,
+    factor REAL NOT NULL,
+
+    FOREIGN KEY(revision) REFERENCES price_revisions(id),
+    FOREIGN KEY(price_id) REFERENCES price(id)
+) STRICT;
+
+CREATE INDEX price_anomalies_revision ON price_anomalies(revision);
+
+ALTER TABLE settings ADD COLUMN anomaly_factor REAL NOT NULL DEFAULT 5.0;
+ALTER TABLE profiles ADD COLUMN use_previous_on_anomaly BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,14 @@
+ALTER TABLE snapshots ADD COLUMN status TEXT NOT NULL DEFAULT 'complete' CHECK (status IN ('pending', 'complete', 'failed'));
+ALTER TABLE snapshots ADD COLUMN error TEXT;
+
+CREATE TABLE snapshot_tabs (
+    snapshot_id INTEGER NOT NULL,
+    stash_id TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+
+    PRIMARY KEY(snapshot_id, stash_id),
+    FOREIGN KEY(snapshot_id) REFERENCES snapshots(id),
+    FOREIGN KEY(stash_id) REFERENCES stashes(id)
+) STRICT;
+
+CREATE INDEX snapshots_status ON snapshots(status);
@@ -0,0 +1 @@
+ALTER TABLE profiles ADD COLUMN snapshot_interval_minutes INTEGER NOT NULL DEFAULT 0;
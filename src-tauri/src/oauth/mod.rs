@@ -4,11 +4,26 @@ use oauth2::{
     AuthUrl, AuthorizationCode, ClientId, CsrfToken, PkceCodeChallenge, RedirectUrl, RefreshToken,
     Scope, TokenResponse, TokenUrl,
 };
+use std::sync::RwLock;
 use tauri::plugin::{Builder, TauriPlugin};
-use tauri::{command, Runtime, Window};
+use tauri::{command, Manager, Runtime, State, Window};
 use tauri_plugin_oauth::{start_with_config, OauthConfig};
 use url::Url;
 
+/// The current access token, shared with the parts of the backend that talk to the PoE API.
+#[derive(Default)]
+pub struct Tokens(RwLock<Option<String>>);
+
+impl Tokens {
+    pub fn access_token(&self) -> Option<String> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, access_token: Option<String>) {
+        *self.0.write().unwrap() = access_token;
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct RefreshResponse {
     auth_token: String,
@@ -67,6 +82,9 @@ async fn do_oauth<R: Runtime>(window: Window<R>) -> Result<u16, String> {
                 .request(http_client)
                 .unwrap();
             println!("{:?}", token_result);
+            window
+                .state::<Tokens>()
+                .set(Some(token_result.access_token().secret().clone()));
             let _ = window.emit("oauth_token", token_result);
         },
     )
@@ -90,14 +108,31 @@ async fn attempt_refresh<R: Runtime>(window: Window<R>, refresh_token: String) {
     match resp {
         Ok(token) => {
             println!("{:?}", token);
+            window
+                .state::<Tokens>()
+                .set(Some(token.access_token().secret().clone()));
             window.emit("oauth_token", token).unwrap();
         }
         Err(_) => (),
     }
 }
 
+/// Hands the token the frontend restored on startup to the backend, `None` on logout.
+#[command]
+fn set_token(tokens: State<'_, Tokens>, access_token: Option<String>) {
+    tokens.set(access_token);
+}
+
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
     Builder::new("oauth")
-        .invoke_handler(tauri::generate_handler![do_oauth, attempt_refresh])
+        .setup(|app| {
+            app.manage(Tokens::default());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            do_oauth,
+            attempt_refresh,
+            set_token
+        ])
         .build()
 }
@@ -0,0 +1,305 @@
+use super::model::{
+    Item, ItemAttribution, ItemChange, ItemChangeGroup, ItemRow, Profile, Snapshot, SnapshotDiff,
+    ValueAttribution,
+};
+use super::pricing::{self, matching, ValuationSettings, Valuer};
+use super::{snapshots, DbCon, Error, Result};
+use sqlx::sqlite::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use tauri::State;
+
+/// What a snapshot holds of one thing: a single item by its PoE id, or every stack of a
+/// stackable item in one tab, as stacks get new ids whenever they are split or merged.
+pub(super) struct Holding {
+    pub stash_id: String,
+    pub item: Item,
+    pub quantity: i64,
+    /// Stored chaos value of everything held.
+    pub value: f64,
+}
+
+fn holding_key(row: &ItemRow) -> String {
+    let item = &row.data.0;
+    match (&item.id, item.stack_size) {
+        (Some(id), None) => format!("id:{}", id),
+        _ => format!("stack:{}:{}:{}", row.stash_id, item.name, item.type_line),
+    }
+}
+
+pub(super) async fn holdings(
+    pool: &SqlitePool,
+    snapshot: &Snapshot,
+) -> Result<HashMap<String, Holding>> {
+    let rows = sqlx::query_as::<_, ItemRow>("SELECT * FROM item WHERE snapshot_id = ?")
+        .bind(snapshot.id)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    let mut holdings = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let holding = holdings
+            .entry(holding_key(&row))
+            .or_insert_with(|| Holding {
+                stash_id: row.stash_id.clone(),
+                item: row.data.0.clone(),
+                quantity: 0,
+                value: 0.0,
+            });
+        holding.quantity += row.data.0.stack_size.unwrap_or(1);
+        holding.value += row.value;
+    }
+
+    Ok(holdings)
+}
+
+/// Broad kind of an item, for grouping changes.
+pub(super) fn category(item: &Item) -> &'static str {
+    if matching::is_map(item) {
+        return "Map";
+    }
+
+    match item.frame_type {
+        0..=2 => "Gear",
+        3 => "Unique",
+        4 => "Gem",
+        5 => "Currency",
+        6 => "Divination Card",
+        _ => "Other",
+    }
+}
+
+pub(super) fn display_name(item: &Item) -> String {
+    if item.name.is_empty() {
+        item.type_line.clone()
+    } else {
+        format!("{} {}", item.name, item.type_line)
+    }
+}
+
+fn change(holding: &Holding, quantity_before: i64, quantity_after: i64, delta: f64) -> ItemChange {
+    ItemChange {
+        stash_id: holding.stash_id.clone(),
+        category: category(&holding.item).to_string(),
+        name: display_name(&holding.item),
+        item: holding.item.clone(),
+        quantity_before,
+        quantity_after,
+        chaos_delta: delta,
+    }
+}
+
+/// The group `change` belongs to, with the change's delta already added.
+fn group_of<'a>(
+    groups: &'a mut BTreeMap<(String, String), ItemChangeGroup>,
+    change: &ItemChange,
+) -> &'a mut ItemChangeGroup {
+    let group = groups
+        .entry((change.stash_id.clone(), change.category.clone()))
+        .or_insert_with(|| ItemChangeGroup {
+            stash_id: change.stash_id.clone(),
+            category: change.category.clone(),
+            added: 0,
+            removed: 0,
+            changed: 0,
+            chaos_delta: 0.0,
+        });
+    group.chaos_delta += change.chaos_delta;
+    group
+}
+
+fn diff(
+    from: &Snapshot,
+    to: &Snapshot,
+    before: &HashMap<String, Holding>,
+    after: &HashMap<String, Holding>,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff {
+        from_snapshot: from.id,
+        to_snapshot: to.id,
+        chaos_delta: 0.0,
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        groups: Vec::new(),
+    };
+
+    for (key, old) in before {
+        match after.get(key) {
+            None => diff.removed.push(change(old, old.quantity, 0, -old.value)),
+            Some(new) if new.quantity != old.quantity => diff.changed.push(change(
+                new,
+                old.quantity,
+                new.quantity,
+                new.value - old.value,
+            )),
+            Some(_) => {}
+        }
+    }
+    for (key, new) in after {
+        if !before.contains_key(key) {
+            diff.added.push(change(new, 0, new.quantity, new.value));
+        }
+    }
+
+    let mut groups = BTreeMap::new();
+    for change in &diff.added {
+        group_of(&mut groups, change).added += 1;
+    }
+    for change in &diff.removed {
+        group_of(&mut groups, change).removed += 1;
+    }
+    for change in &diff.changed {
+        group_of(&mut groups, change).changed += 1;
+    }
+    diff.chaos_delta = groups.values().map(|g| g.chaos_delta).sum();
+    diff.groups = groups.into_values().collect();
+
+    // Largest changes first
+    for changes in [&mut diff.added, &mut diff.removed, &mut diff.changed] {
+        changes.sort_by(|a, b| b.chaos_delta.abs().total_cmp(&a.chaos_delta.abs()));
+    }
+
+    diff
+}
+
+/// Items gained, lost and changed in quantity from snapshot `a` to snapshot `b`, valued at
+/// the prices stored with each snapshot.
+#[tauri::command]
+pub(super) async fn diff_snapshots(con: State<'_, DbCon>, a: i64, b: i64) -> Result<SnapshotDiff> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    let from = snapshots::load_complete(pool, a).await?;
+    let to = snapshots::load_complete(pool, b).await?;
+    let before = holdings(pool, &from).await?;
+    let after = holdings(pool, &to).await?;
+
+    Ok(diff(&from, &to, &before, &after))
+}
+
+/// Chaos value of one unit of `item`, or 0 if the profile leaves its price out of totals.
+fn unit_value(valuer: &Valuer, item: &Item) -> f64 {
+    let value = valuer.value(item);
+    if value.excluded(&valuer.settings) {
+        0.0
+    } else {
+        value.value / item.stack_size.unwrap_or(1) as f64
+    }
+}
+
+fn attribution(holding: &Holding, before: (i64, f64), after: (i64, f64)) -> ItemAttribution {
+    let ((quantity_before, unit_before), (quantity_after, unit_after)) = (before, after);
+    let held_in_both = quantity_before > 0 && quantity_after > 0;
+
+    ItemAttribution {
+        stash_id: holding.stash_id.clone(),
+        category: category(&holding.item).to_string(),
+        name: display_name(&holding.item),
+        quantity_before,
+        quantity_after,
+        unit_price_before: unit_before,
+        unit_price_after: unit_after,
+        quantity_effect: if held_in_both {
+            (quantity_after - quantity_before) as f64 * unit_before
+        } else {
+            0.0
+        },
+        price_effect: if held_in_both {
+            quantity_after as f64 * (unit_after - unit_before)
+        } else {
+            0.0
+        },
+        total: quantity_after as f64 * unit_after - quantity_before as f64 * unit_before,
+    }
+}
+
+/// Splits the change in value from snapshot `a` to snapshot `b` into changed quantities,
+/// changed prices and items that appeared or disappeared. Each snapshot is valued at its own
+/// pricing revision with the settings of `b`'s profile.
+#[tauri::command]
+pub(super) async fn attribute_value_change(
+    con: State<'_, DbCon>,
+    a: i64,
+    b: i64,
+) -> Result<ValueAttribution> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    let from = snapshots::load_complete(pool, a).await?;
+    let to = snapshots::load_complete(pool, b).await?;
+
+    let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
+        .bind(to.profile_id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)?;
+    let settings = ValuationSettings::from(&profile);
+
+    let from_revision = pricing::revision(pool, from.pricing_revision).await?;
+    let to_revision = pricing::revision(pool, to.pricing_revision).await?;
+    let valuer_before = Valuer::load(pool, &con.prices, &from_revision, settings).await?;
+    let valuer_after = Valuer::load(pool, &con.prices, &to_revision, settings).await?;
+
+    let before = holdings(pool, &from).await?;
+    let after = holdings(pool, &to).await?;
+
+    let mut result = ValueAttribution {
+        from_snapshot: from.id,
+        to_snapshot: to.id,
+        from_revision: from_revision.id,
+        to_revision: to_revision.id,
+        value_before: 0.0,
+        value_after: 0.0,
+        quantity_effect: 0.0,
+        price_effect: 0.0,
+        appeared: 0.0,
+        disappeared: 0.0,
+        items: Vec::new(),
+    };
+
+    for (key, old) in &before {
+        let unit_before = unit_value(&valuer_before, &old.item);
+        let item = match after.get(key) {
+            Some(new) => attribution(
+                new,
+                (old.quantity, unit_before),
+                (new.quantity, unit_value(&valuer_after, &new.item)),
+            ),
+            None => {
+                let item = attribution(old, (old.quantity, unit_before), (0, 0.0));
+                result.disappeared += item.total;
+                item
+            }
+        };
+        result.items.push(item);
+    }
+    for (key, new) in &after {
+        if !before.contains_key(key) {
+            let item = attribution(
+                new,
+                (0, 0.0),
+                (new.quantity, unit_value(&valuer_after, &new.item)),
+            );
+            result.appeared += item.total;
+            result.items.push(item);
+        }
+    }
+
+    for item in &result.items {
+        result.value_before += item.quantity_before as f64 * item.unit_price_before;
+        result.value_after += item.quantity_after as f64 * item.unit_price_after;
+        result.quantity_effect += item.quantity_effect;
+        result.price_effect += item.price_effect;
+    }
+
+    // Only what moved, largest first
+    result
+        .items
+        .retain(|i| i.total != 0.0 || i.price_effect != 0.0);
+    result
+        .items
+        .sort_by(|a, b| b.total.abs().total_cmp(&a.total.abs()));
+
+    Ok(result)
+}
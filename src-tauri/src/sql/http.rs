@@ -0,0 +1,124 @@
+use super::Result;
+use reqwest::header::{
+    HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER,
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::sync::RwLock;
+use std::time::Duration;
+
+pub(super) static USER_AGENT: &str = "OAuth loothound/0.1 (contact: paul.kosel@rub.de) StrictMode";
+
+/// Attempts after the first one before a failing request is given up on.
+const MAX_RETRIES: u32 = 4;
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// The HTTP client shared by everything the backend downloads, rebuilt whenever the
+/// timeout setting changes.
+pub(super) struct HttpClient(RwLock<Client>);
+
+fn build(timeout_seconds: i64) -> Result<Client> {
+    Ok(Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(timeout_seconds.max(1) as u64))
+        .build()?)
+}
+
+impl HttpClient {
+    pub fn new(timeout_seconds: i64) -> Result<Self> {
+        Ok(HttpClient(RwLock::new(build(timeout_seconds)?)))
+    }
+
+    pub fn configure(&self, timeout_seconds: i64) -> Result<()> {
+        *self.0.write().unwrap() = build(timeout_seconds)?;
+        Ok(())
+    }
+
+    /// Clients are reference counted, so this is cheap and never blocks on a request.
+    pub fn client(&self) -> Client {
+        self.0.read().unwrap().clone()
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BASE_DELAY * 2u32.pow(attempt)
+}
+
+pub(super) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Sends `request`, retrying with exponential backoff on connection problems, timeouts,
+/// rate limiting and server errors. A `Retry-After` header is honoured over the backoff.
+///
+/// Whatever the last attempt returned is passed on, so callers still check the status.
+pub(super) async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt = 0;
+
+    loop {
+        let current = match request.try_clone() {
+            Some(current) => current,
+            None => return request.send().await,
+        };
+        let result = current.send().await;
+
+        let delay = match &result {
+            Ok(resp)
+                if resp.status() == StatusCode::TOO_MANY_REQUESTS
+                    || resp.status().is_server_error() =>
+            {
+                retry_after(resp.headers()).unwrap_or_else(|| backoff(attempt))
+            }
+            Err(e) if e.is_timeout() || e.is_connect() => backoff(attempt),
+            _ => return result,
+        };
+
+        if attempt >= MAX_RETRIES {
+            return result;
+        }
+
+        tokio::time::sleep(delay.min(MAX_DELAY)).await;
+        attempt += 1;
+    }
+}
+
+/// Cache validators of a previous response, sent back so unchanged resources answer with
+/// `304 Not Modified` instead of the full body.
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn of(resp: &Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
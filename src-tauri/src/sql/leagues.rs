@@ -0,0 +1,175 @@
+use super::http::{self, HttpClient};
+use super::{DbCon, Error, Result};
+use crate::sql::model::League;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use sqlx::sqlite::SqlitePool;
+use tauri::State;
+
+#[derive(Debug, serde::Deserialize)]
+struct NinjaLeague {
+    name: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NinjaIndexState {
+    economy_leagues: Vec<NinjaLeague>,
+    old_economy_leagues: Vec<NinjaLeague>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoeLeague {
+    id: String,
+    realm: Option<String>,
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PoeLeagueResponse {
+    leagues: Vec<PoeLeague>,
+}
+
+/// Leagues poe.ninja currently has economy data for and that have not ended yet.
+pub(super) async fn active_pricing_leagues(pool: &SqlitePool) -> Result<Vec<String>> {
+    let leagues = sqlx::query_as::<_, (String,)>(
+        "SELECT id FROM leagues WHERE ninja_indexed = 1 AND (end_at IS NULL OR end_at > ?) ORDER BY start_at IS NULL, start_at",
+    )
+    .bind(Utc::now().naive_utc())
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(leagues.into_iter().map(|l| l.0).collect())
+}
+
+/// The pricing leagues and providers profiles use, as `(league, provider)` pairs, leaving
+/// out leagues that are no longer active.
+pub(super) async fn used_pricing_sources(pool: &SqlitePool) -> Result<Vec<(String, String)>> {
+    let active = active_pricing_leagues(pool).await?;
+    let used = sqlx::query_as::<_, (String, String)>(
+        "SELECT DISTINCT pricing_league, price_provider FROM profiles",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(used
+        .into_iter()
+        .filter(|(league, _)| active.contains(league))
+        .collect())
+}
+
+pub(super) async fn validate_pricing_league(pool: &SqlitePool, league: &str) -> Result<()> {
+    if active_pricing_leagues(pool).await?.iter().any(|l| l == league) {
+        Ok(())
+    } else {
+        Err(Error::UnknownLeague(league.to_string()))
+    }
+}
+
+async fn fetch_ninja_index_state(client: &Client) -> Result<NinjaIndexState> {
+    Ok(
+        http::send(client.get("https://poe.ninja/api/data/getindexstate"))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?,
+    )
+}
+
+async fn fetch_account_leagues(client: &Client, token: &str) -> Result<Vec<PoeLeague>> {
+    let resp: PoeLeagueResponse = http::send(
+        client
+            .get("https://api.pathofexile.com/account/leagues")
+            .bearer_auth(token),
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+    Ok(resp.leagues)
+}
+
+#[tauri::command]
+pub(super) async fn refresh_leagues(
+    con: State<'_, DbCon>,
+    http: State<'_, HttpClient>,
+    token: Option<String>,
+) -> Result<Vec<League>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+    let client = http.client();
+
+    let index_state = fetch_ninja_index_state(&client).await;
+    let account_leagues = match token {
+        Some(token) => fetch_account_leagues(&client, &token).await.map(Some),
+        None => Ok(None),
+    };
+
+    // Either source is enough to refresh from, e.g. when the token has expired
+    let (index_state, account_leagues) = match (index_state, account_leagues) {
+        (Err(e), Err(_) | Ok(None)) => return Err(e),
+        (index_state, account_leagues) => (
+            index_state.ok(),
+            account_leagues.ok().flatten().unwrap_or_default(),
+        ),
+    };
+
+    let now = Utc::now().naive_utc();
+    let mut trx = pool.begin().await?;
+
+    for league in account_leagues.iter() {
+        sqlx::query(
+            "INSERT INTO leagues (id, realm, start_at, end_at, updated_at) VALUES (?, ?, ?, ?, ?) ON CONFLICT(id) DO UPDATE SET realm=excluded.realm, start_at=excluded.start_at, end_at=excluded.end_at, updated_at=excluded.updated_at",
+        )
+        .bind(&league.id)
+        .bind(&league.realm)
+        .bind(league.start_at.map(|d| d.naive_utc()))
+        .bind(league.end_at.map(|d| d.naive_utc()))
+        .bind(now)
+        .execute(&mut trx)
+        .await
+        .map_err(Error::Sql)?;
+    }
+
+    for (leagues, indexed) in index_state.iter().flat_map(|state| {
+        [
+            (&state.economy_leagues, true),
+            (&state.old_economy_leagues, false),
+        ]
+    }) {
+        for league in leagues.iter() {
+            sqlx::query(
+                "INSERT INTO leagues (id, ninja_indexed, updated_at) VALUES (?, ?, ?) ON CONFLICT(id) DO UPDATE SET ninja_indexed=excluded.ninja_indexed, updated_at=excluded.updated_at",
+            )
+            .bind(&league.name)
+            .bind(indexed)
+            .bind(now)
+            .execute(&mut trx)
+            .await
+            .map_err(Error::Sql)?;
+        }
+    }
+
+    trx.commit().await?;
+
+    sqlx::query_as::<_, League>("SELECT * FROM leagues ORDER BY start_at IS NULL, start_at")
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn get_leagues(con: State<'_, DbCon>) -> Result<Vec<League>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, League>("SELECT * FROM leagues ORDER BY start_at IS NULL, start_at")
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)
+}
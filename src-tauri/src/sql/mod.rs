@@ -1,6 +1,17 @@
+mod diff;
+mod http;
+mod leagues;
 mod model;
+mod overrides;
+mod poe;
+mod pricing;
+mod scheduler;
+mod settings;
+mod snapshots;
+mod valuation;
 
 use chrono::Duration;
+use http::HttpClient;
 use model::*;
 use serde::Serialize;
 use sqlx::migrate::MigrateDatabase;
@@ -8,12 +19,13 @@ use sqlx::sqlite::{Sqlite, SqlitePool};
 use std::{fs::create_dir_all, path::PathBuf};
 use tauri::{
     plugin::{Builder, TauriPlugin},
-    AppHandle, Manager, Runtime, State,
+    AppHandle, Manager, Runtime, State, Window,
 };
 use tokio::sync::Mutex;
 
 struct DbCon {
     db: Mutex<Option<SqlitePool>>,
+    prices: pricing::PriceCache,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -29,6 +41,42 @@ enum Error {
 
     #[error(transparent)]
     Network(#[from] reqwest::Error),
+
+    #[error("Unknown or inactive pricing league: {0}")]
+    UnknownLeague(String),
+
+    #[error("Unknown price provider: {0}")]
+    UnknownProvider(String),
+
+    #[error("No prices have been fetched for {0} yet")]
+    NoPriceRevision(String),
+
+    #[error("Pricing revision {0} is not a revision of {1}")]
+    RevisionLeagueMismatch(i64, String),
+
+    #[error("Pricing revision {0} is not from {1}")]
+    RevisionProviderMismatch(i64, String),
+
+    #[error("Anomaly factor must be above 1, or 0 to turn detection off, not {0}")]
+    InvalidAnomalyFactor(f64),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error("Can't import {0}")]
+    InvalidImport(String),
+
+    #[error("Not logged in to Path of Exile")]
+    NotLoggedIn,
+
+    #[error("Snapshot {0} can't be resumed, it hasn't failed")]
+    SnapshotNotResumable(i64),
+
+    #[error("Snapshot {0} is missing tabs, it hasn't completed")]
+    SnapshotIncomplete(i64),
 }
 
 impl Serialize for Error {
@@ -42,66 +90,6 @@ impl Serialize for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct Sparkline {
-    data: Vec<f64>,
-    total_change: f64,
-}
-
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ItemLine {
-    name: String,
-    chaos_value: f64,
-    links: Option<isize>,
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct NinjaItemResponse {
-    lines: Vec<ItemLine>,
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct CurrencyReceive {
-    value: f64,
-}
-
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct CurrencyLine {
-    currency_type_name: String,
-    receive: Option<CurrencyReceive>,
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct NinjaCurrencyResponse {
-    lines: Vec<CurrencyLine>,
-}
-
-static CURRENCY_CATEGORIES: [&str; 2] = ["Currency", "Fragment"];
-static ITEM_CATEGORIES: [&str; 18] = [
-    "DivinationCard",
-    "Artifact",
-    "Oil",
-    "Incubator",
-    "UniqueWeapon",
-    "UniqueArmour",
-    "UniqueAccessory",
-    "UniqueFlask",
-    "UniqueJewel",
-    "UniqueMap",
-    "DeliriumOrb",
-    "Invitation",
-    "Scarab",
-    "Fossil",
-    "Resonator",
-    "Beast",
-    "Essence",
-    "Vial",
-];
-static LEAGUES: [&str; 2] = ["Standard", "Ancestor"];
-
 fn app_path<R: Runtime>(app: &AppHandle<R>) -> PathBuf {
     app.path_resolver()
         .app_data_dir()
@@ -142,6 +130,7 @@ async fn insert_stash(
     .map_err(Error::Sql)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn create_profile(
     con: State<'_, DbCon>,
@@ -149,18 +138,35 @@ async fn create_profile(
     stash_tabs: Vec<String>,
     league_id: String,
     pricing_league: String,
+    price_provider: Option<String>,
+    valuation_mode: Option<ValuationMode>,
+    min_listing_count: Option<i64>,
+    exclude_low_confidence: Option<bool>,
+    use_previous_on_anomaly: Option<bool>,
+    snapshot_interval_minutes: Option<i64>,
 ) -> Result<Profile> {
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
+    let price_provider = price_provider.unwrap_or_else(|| pricing::DEFAULT_PROVIDER.to_string());
+
+    leagues::validate_pricing_league(pool, &pricing_league).await?;
+    pricing::validate_provider(&price_provider)?;
+
     let trx = pool.begin().await?;
 
     let profile = sqlx::query_as::<_, Profile>(
-        "INSERT INTO profiles (name, league_id, pricing_league) VALUES (?, ?, ?) RETURNING *",
+        "INSERT INTO profiles (name, league_id, pricing_league, price_provider, valuation_mode, min_listing_count, exclude_low_confidence, use_previous_on_anomaly, snapshot_interval_minutes) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
     )
     .bind(profile_name)
     .bind(league_id)
     .bind(pricing_league)
+    .bind(price_provider)
+    .bind(valuation_mode.unwrap_or_default())
+    .bind(min_listing_count.unwrap_or(0))
+    .bind(exclude_low_confidence.unwrap_or(false))
+    .bind(use_previous_on_anomaly.unwrap_or(false))
+    .bind(snapshots::snapshot_interval(snapshot_interval_minutes.unwrap_or(0)))
     .fetch_one(pool)
     .await?;
 
@@ -207,198 +213,30 @@ async fn get_profiles(con: State<'_, DbCon>) -> Result<Vec<ProfileWithStashes>>
 }
 
 #[tauri::command]
-async fn new_snapshot(con: State<'_, DbCon>, profile_id: i64) -> Result<Snapshot> {
-    let mutex = con.db.lock().await;
-    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
-
-    let pricing_revision = sqlx::query_as::<_, (i64,)>("SELECT MAX(revision) as rev FROM price")
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    sqlx::query(
-        "INSERT INTO snapshots (profile_id, timestamp, pricing_revision, value) VALUES (?, ?, ?, ?)",
-    )
-    .bind(profile_id)
-    .bind(chrono::Local::now().naive_local())
-    .bind(pricing_revision.0)
-    .bind(0.0)
-    .execute(pool)
-    .await
-    .map_err(Error::Sql)?;
-
-    sqlx::query_as::<_, Snapshot>(
-        "SELECT * FROM snapshots WHERE profile_id = ? ORDER BY id DESC LIMIT 1",
-    )
-    .bind(profile_id)
-    .fetch_one(pool)
-    .await
-    .map_err(Error::Sql)
-}
-
-#[tauri::command]
-async fn add_items_to_snapshot(
+async fn fetch_prices<R: Runtime>(
+    window: Window<R>,
     con: State<'_, DbCon>,
-    snapshot: Snapshot,
-    items: Vec<Item>,
-    stash_id: String,
-) -> Result<f64> {
-    let mutex = con.db.lock().await;
-    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
-    let snapshot_id = snapshot.id;
-
-    let mut counter = 0.0;
-
-    let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
-        .bind(snapshot.profile_id)
-        .fetch_one(pool)
+    http: State<'_, HttpClient>,
+) -> Result<()> {
+    // Clone the pool so commands aren't locked out while prices download
+    let pool = con
+        .db
+        .lock()
         .await
-        .map_err(Error::Sql)?;
-
-    let league = profile.pricing_league;
-
-    for item in items {
-        let json_item = serde_json::to_string(&item).unwrap();
-        let name = if item.name.len() > 0 {
-            item.name
-        } else {
-            item.type_line
+        .clone()
+        .ok_or(Error::DatabaseNotLoaded)?;
+    let http = http.client();
+
+    for (league, provider_id) in leagues::used_pricing_sources(&pool).await? {
+        let provider = match pricing::provider(&provider_id) {
+            Some(provider) => provider,
+            None => continue,
         };
-        let mut price = sqlx::query_as::<_, Price>(
-            "SELECT * FROM price WHERE name LIKE ? AND revision = ? AND league = ? LIMIT 1",
-        )
-        .bind(&name)
-        .bind(snapshot.pricing_revision)
-        .bind(&league)
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)
-        .map_or(0.0, |x| x.price);
-
-        if &name == "Chaos Orb" {
-            price = 1.0;
-        }
-
-        counter += price * item.stack_size.unwrap_or(1) as f64;
-
-        sqlx::query("INSERT INTO item (snapshot_id, stash_id, data, value) VALUES (?, ?, ?, ?)")
-            .bind(snapshot_id)
-            .bind(&stash_id)
-            .bind(json_item)
-            .bind(price * item.stack_size.unwrap_or(1) as f64)
-            .execute(pool)
-            .await
-            .map_err(Error::Sql)?;
-    }
-
-    let currentSnapshotValue = sqlx::query_as::<_, (f64,)>("SELECT value FROM snapshots WHERE id = ?")
-        .bind(snapshot.id)
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)?;
-    counter += currentSnapshotValue.0;
-
-    sqlx::query("UPDATE snapshots SET value = ? WHERE id = ?")
-        .bind(counter)
-        .bind(snapshot.id)
-        .execute(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    Ok(
-        sqlx::query_as::<_, (f64,)>("SELECT value FROM snapshots WHERE id = ?")
-            .bind(snapshot.id)
-            .fetch_one(pool)
-            .await
-            .map_err(Error::Sql)?
-            .0,
-    )
-}
 
-#[tauri::command]
-async fn snapshot_set_value(con: State<'_, DbCon>, snapshot: Snapshot, value: i64) -> Result<()> {
-    let mutex = con.db.lock().await;
-    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
-
-    sqlx::query("UPDATE snapshots SET value = ? WHERE id = ?")
-        .bind(value)
-        .bind(snapshot.id)
-        .execute(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    Ok(())
-}
-
-#[tauri::command]
-async fn fetch_prices(con: State<'_, DbCon>) -> Result<()> {
-    let mutex = con.db.lock().await;
-    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
-
-    async fn fetch_prices_for_league(pool: &SqlitePool, league: String) -> Result<()> {
-        let current_revision: (i64,) =
-            sqlx::query_as("SELECT MAX(revision) as rev FROM price WHERE league = ?")
-                .bind(&league)
-                .fetch_one(pool)
-                .await
-                .map_err(Error::Sql)
-                .unwrap_or((0,));
-        let next_rev = current_revision.0 + 1;
-
-        let timestamp = chrono::Local::now().naive_utc();
-
-        for currency_type in CURRENCY_CATEGORIES {
-            let url = format!(
-                "https://poe.ninja/api/data/currencyoverview?league={}&type={}",
-                league, currency_type
-            );
-            let resp: NinjaCurrencyResponse = reqwest::get(url).await?.json().await?;
-
-            for line in resp.lines.iter() {
-                if let Some(receive) = &line.receive {
-                    sqlx::query(
-                        "INSERT INTO price (name, price, revision, timestamp, league) VALUES (?, ?, ?, ?, ?)",
-                    )
-                    .bind(&line.currency_type_name)
-                    .bind(receive.value)
-                    .bind(next_rev)
-                    .bind(timestamp)
-                    .bind(&league)
-                    .execute(pool)
-                    .await
-                    .map_err(Error::Sql)?;
-                }
-            }
-        }
-
-        for item_type in ITEM_CATEGORIES {
-            let url = format!(
-                "https://poe.ninja/api/data/itemoverview?league={}&type={}",
-                league, item_type
-            );
-            let resp: NinjaItemResponse = reqwest::get(url).await?.json().await?;
-
-            for line in resp.lines.iter() {
-                let links = line.links.map(|n| if n == 6 { 1 } else { 0 }).unwrap_or(0);
-                sqlx::query(
-                    "INSERT INTO price (name, price, revision, fully_linked, timestamp, league) VALUES (?, ?, ?, ?, ?, ?)",
-                )
-                .bind(&line.name)
-                .bind(line.chaos_value)
-                .bind(next_rev)
-                .bind(links)
-                .bind(timestamp)
-                .bind(&league)
-                .execute(pool)
-                .await
-                .map_err(Error::Sql)?;
-            }
-        }
-        Ok(())
-    }
-
-    for league in LEAGUES.iter() {
-        fetch_prices_for_league(&pool, league.to_string()).await?;
+        pricing::fetch_and_store(&pool, &http, &league, provider.as_ref(), |progress| {
+            let _ = window.emit("price_fetch_progress", progress);
+        })
+        .await?;
     }
 
     Ok(())
@@ -409,8 +247,9 @@ async fn list_snapshots(con: State<'_, DbCon>, profile_id: i64) -> Result<Vec<Sn
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
-    sqlx::query_as::<_, Snapshot>("SELECT * FROM snapshots WHERE profile_id = ?")
+    sqlx::query_as::<_, Snapshot>("SELECT * FROM snapshots WHERE profile_id = ? AND status = ?")
         .bind(profile_id)
+        .bind(SnapshotStatus::Complete)
         .fetch_all(pool)
         .await
         .map_err(Error::Sql)
@@ -421,17 +260,9 @@ async fn delete_snapshot(con: State<'_, DbCon>, snapshot_id: i64) -> Result<()>
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
-    sqlx::query("DELETE FROM item WHERE snapshot_id = ?")
-        .bind(snapshot_id)
-        .execute(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    sqlx::query("DELETE FROM snapshots WHERE id = ?")
-        .bind(snapshot_id)
-        .execute(pool)
-        .await
-        .map_err(Error::Sql)?;
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+    snapshots::delete(&mut trx, snapshot_id).await?;
+    trx.commit().await.map_err(Error::Sql)?;
 
     Ok(())
 }
@@ -447,19 +278,17 @@ async fn delete_profile(con: State<'_, DbCon>, profile_id: i64) -> Result<()> {
         .await
         .map_err(Error::Sql)?;
 
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
     for snapshot in snapshots {
-        sqlx::query("DELETE FROM item WHERE snapshot_id = ?")
-            .bind(snapshot.id)
-            .execute(pool)
-            .await
-            .map_err(Error::Sql)?;
-
-        sqlx::query("DELETE FROM snapshots WHERE id = ?")
-            .bind(snapshot.id)
-            .execute(pool)
-            .await
-            .map_err(Error::Sql)?;
+        snapshots::delete(&mut trx, snapshot.id).await?;
     }
+    trx.commit().await.map_err(Error::Sql)?;
+
+    sqlx::query("DELETE FROM price_overrides WHERE profile_id = ?")
+        .bind(profile_id)
+        .execute(pool)
+        .await
+        .map_err(Error::Sql)?;
 
     sqlx::query("DELETE FROM profiles WHERE id = ?")
         .bind(profile_id)
@@ -479,12 +308,30 @@ async fn update_profile(
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
+    let stored = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
+        .bind(profile.id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    // A league that has since ended can be kept, it just can't be switched to
+    if profile.pricing_league != stored.pricing_league {
+        leagues::validate_pricing_league(pool, &profile.pricing_league).await?;
+    }
+    pricing::validate_provider(&profile.price_provider)?;
+
     let new_profile = sqlx::query_as::<_, Profile>(
-        "UPDATE profiles SET name = ?, league_id = ?, pricing_league = ? WHERE id = ? RETURNING *",
+        "UPDATE profiles SET name = ?, league_id = ?, pricing_league = ?, price_provider = ?, valuation_mode = ?, min_listing_count = ?, exclude_low_confidence = ?, use_previous_on_anomaly = ?, snapshot_interval_minutes = ? WHERE id = ? RETURNING *",
     )
     .bind(profile.name)
     .bind(profile.league_id)
     .bind(profile.pricing_league)
+    .bind(profile.price_provider)
+    .bind(profile.valuation_mode)
+    .bind(profile.min_listing_count)
+    .bind(profile.exclude_low_confidence)
+    .bind(profile.use_previous_on_anomaly)
+    .bind(snapshots::snapshot_interval(profile.snapshot_interval_minutes))
     .bind(profile.id)
     .fetch_one(pool)
     .await
@@ -516,24 +363,27 @@ async fn has_recent_prices(con: State<'_, DbCon>) -> Result<bool> {
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
-    let count = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) as count_price FROM price")
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    if count.0 == 0 {
-        return Ok(false);
+    let res = match sqlx::query_as::<_, PriceRevision>(
+        "SELECT * FROM price_revisions WHERE source IS NULL ORDER BY timestamp DESC LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(Error::Sql)?
+    {
+        Some(res) => res,
+        None => return Ok(false),
+    };
+
+    let settings = settings::load(pool).await?;
+    // With automatic refreshes off any prices will do until they are fetched by hand
+    if settings.price_refresh_minutes == 0 {
+        return Ok(true);
     }
 
-    let res = sqlx::query_as::<_, Price>("SELECT * FROM price ORDER BY timestamp DESC LIMIT 1")
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)?;
-
-    return Ok(chrono::Utc::now()
+    Ok(chrono::Utc::now()
         .naive_utc()
         .signed_duration_since(res.timestamp)
-        <= Duration::hours(1));
+        <= Duration::minutes(settings.price_refresh_minutes))
 }
 
 #[tauri::command]
@@ -568,6 +418,7 @@ async fn oopsie<R: Runtime>(con: State<'_, DbCon>, app: AppHandle<R>) -> Result<
     sqlx::migrate!().run(&new_pool).await?;
 
     *mutex = Some(new_pool);
+    con.prices.clear();
 
     Ok(())
 }
@@ -580,25 +431,18 @@ async fn basically_this_use_effect(
     let mutex = con.db.lock().await;
     let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
 
-    let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
-        .bind(snapshot.profile_id)
-        .fetch_one(pool)
-        .await
-        .map_err(Error::Sql)?;
-    let league = profile.pricing_league;
-
     let item_rows = sqlx::query_as::<_, ItemRow>("SELECT * FROM item WHERE snapshot_id = ?")
         .bind(snapshot.id)
         .fetch_all(pool)
         .await
         .map_err(Error::Sql)?;
 
+    // Revisions belong to one league and provider, which the profile may have left since
     let snapshot_div_price = sqlx::query_as::<_, Price>(
-        "SELECT  * FROM price WHERE name = ? AND revision = ? AND LEAGUE = ? LIMIT 1",
+        "SELECT * FROM price WHERE name = ? AND revision = ? LIMIT 1",
     )
     .bind("Divine Orb")
     .bind(snapshot.pricing_revision)
-    .bind(league)
     .fetch_one(pool)
     .await
     .map_err(Error::Sql)?;
@@ -609,10 +453,15 @@ async fn basically_this_use_effect(
             .map(|x| ItemWithPrice {
                 item: x.data.clone().0,
                 price: x.value,
+                change: x.price_change,
+                low_confidence: x.low_confidence,
+                listed_price: x.listed_value,
             })
             .collect(),
         total_chaos: snapshot.value,
         total_div: snapshot.value / snapshot_div_price.price,
+        low_confidence_chaos: snapshot.low_confidence_value,
+        listed_chaos: snapshot.listed_value,
     })
 }
 
@@ -629,8 +478,11 @@ async fn stash_from_id(con: State<'_, DbCon>, stash_id: String) -> Result<Stash>
 }
 
 #[tauri::command]
-async fn get_pricing_leagues() -> Result<Vec<String>> {
-    Ok(LEAGUES.iter().map(|x| x.to_string()).collect())
+async fn get_pricing_leagues(con: State<'_, DbCon>) -> Result<Vec<String>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    leagues::active_pricing_leagues(pool).await
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
@@ -647,12 +499,19 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
                 }
                 let pool = SqlitePool::connect(&db_path).await?;
                 sqlx::migrate!().run(&pool).await?;
+                snapshots::recover(&pool).await?;
+                let settings = settings::load(&pool).await?;
+                app.manage(HttpClient::new(settings.http_timeout_seconds)?);
+                app.manage(poe::RateLimiter::default());
                 app.manage(DbCon {
                     db: Mutex::new(Some(pool)),
+                    prices: Default::default(),
                 });
                 Result::<()>::Ok(())
             })?;
 
+            scheduler::spawn(app.clone());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -660,10 +519,7 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             create_profile,
             insert_stash,
             get_profiles,
-            new_snapshot,
             fetch_prices,
-            add_items_to_snapshot,
-            snapshot_set_value,
             list_snapshots,
             delete_snapshot,
             delete_profile,
@@ -672,8 +528,29 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             snapshot_fetch_items,
             oopsie,
             basically_this_use_effect,
+            diff::diff_snapshots,
+            diff::attribute_value_change,
+            snapshots::snapshot_profile,
+            snapshots::resume_snapshot,
+            snapshots::list_failed_snapshots,
             stash_from_id,
-            get_pricing_leagues
+            get_pricing_leagues,
+            leagues::refresh_leagues,
+            leagues::get_leagues,
+            overrides::get_price_overrides,
+            overrides::create_price_override,
+            overrides::update_price_override,
+            overrides::delete_price_override,
+            pricing::get_price_providers,
+            pricing::get_price_history,
+            pricing::get_price_revisions,
+            pricing::get_price_anomalies,
+            pricing::import_prices,
+            pricing::prune_price_history,
+            settings::get_settings,
+            settings::update_settings,
+            valuation::revalue_snapshot,
+            valuation::list_valuations
         ])
         .build()
 }
@@ -1,4 +1,5 @@
 use sqlx::FromRow;
+use std::collections::HashMap;
 use ts_rs::TS;
 
 #[derive(FromRow, Debug, PartialEq, Eq, serde::Serialize, TS)]
@@ -17,6 +18,31 @@ pub struct Profile {
     pub name: String,
     pub league_id: String,
     pub pricing_league: String,
+    pub price_provider: String,
+    pub valuation_mode: ValuationMode,
+    pub min_listing_count: i64,
+    pub exclude_low_confidence: bool,
+    /// Value lines flagged as anomalies at the price of the revision before.
+    pub use_previous_on_anomaly: bool,
+    /// Minutes between snapshots taken automatically while the app is open, 0 for none.
+    pub snapshot_interval_minutes: i64,
+}
+
+/// Which side of the market items are valued at.
+///
+/// `Receive` is what selling to existing buy orders yields (a liquidation value),
+/// `Pay` what buying the items back would cost (a replacement value).
+#[derive(
+    sqlx::Type, Debug, PartialEq, Eq, Clone, Copy, Default, serde::Serialize, serde::Deserialize, TS,
+)]
+#[ts(export, export_to = "../src/bindings/")]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ValuationMode {
+    #[default]
+    Receive,
+    Pay,
+    Mid,
 }
 
 #[derive(Debug, PartialEq, Eq, serde::Serialize, TS)]
@@ -33,7 +59,7 @@ pub struct ProfileStashAssoc {
     pub stash_id: String,
 }
 
-#[derive(FromRow, Debug, PartialEq, serde::Serialize, serde::Deserialize, TS)]
+#[derive(FromRow, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, TS)]
 #[ts(export, export_to = "../src/bindings/")]
 pub struct Snapshot {
     pub id: i64,
@@ -42,6 +68,25 @@ pub struct Snapshot {
     pub timestamp: sqlx::types::chrono::NaiveDateTime,
     pub pricing_revision: i64,
     pub value: f64,
+    pub low_confidence_value: f64,
+    pub listed_value: f64,
+    pub status: SnapshotStatus,
+    /// Why the snapshot failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Where a snapshot is in its lifecycle. Tabs are written one at a time while it is
+/// `Pending`, and only `Complete` snapshots hold every tab of their profile.
+#[derive(
+    sqlx::Type, Debug, PartialEq, Eq, Clone, Copy, serde::Serialize, serde::Deserialize, TS,
+)]
+#[ts(export, export_to = "../src/bindings/")]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum SnapshotStatus {
+    Pending,
+    Complete,
+    Failed,
 }
 
 #[derive(FromRow, Debug, PartialEq)]
@@ -51,6 +96,9 @@ pub struct ItemRow {
     pub stash_id: String,
     pub data: sqlx::types::Json<Item>,
     pub value: f64,
+    pub price_change: Option<f64>,
+    pub low_confidence: bool,
+    pub listed_value: Option<f64>,
 }
 
 #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, Clone, TS)]
@@ -73,11 +121,40 @@ pub struct Item {
     pub type_line: String,
     pub base_type: String,
     pub identified: bool,
+    #[serde(alias = "ilvl")]
     pub item_level: Option<i64>,
     pub frame_type: i64,
+    pub corrupted: Option<bool>,
+    pub sockets: Option<Vec<ItemSocket>>,
+    pub properties: Option<Vec<ItemProperty>>,
+    pub influences: Option<HashMap<String, bool>>,
+    pub enchant_mods: Option<Vec<String>>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, Clone, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+#[serde(rename_all = "camelCase")]
+pub struct ItemSocket {
+    pub group: i64,
+    pub attr: Option<String>,
+    pub s_colour: Option<String>,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, Clone, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+#[serde(rename_all = "camelCase")]
+pub struct ItemProperty {
+    pub name: String,
+    #[ts(type = "any[]")]
+    pub values: Vec<(String, i64)>,
+    pub display_mode: Option<i64>,
+    pub progress: Option<f64>,
+    pub r#type: Option<i64>,
+    pub suffix: Option<String>,
 }
 
-#[derive(FromRow, Debug, PartialEq, serde::Serialize, serde::Deserialize, TS)]
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
 #[ts(export, export_to = "../src/bindings/")]
 pub struct Price {
     pub id: i64,
@@ -88,6 +165,149 @@ pub struct Price {
     #[ts(type = "string")]
     pub timestamp: chrono::NaiveDateTime,
     pub league: String,
+    pub provider: String,
+    pub category: Option<String>,
+    pub base_type: Option<String>,
+    pub variant: Option<String>,
+    pub links: Option<i64>,
+    pub gem_level: Option<i64>,
+    pub gem_quality: Option<i64>,
+    pub gem_quality_type: Option<String>,
+    pub corrupted: Option<bool>,
+    pub map_tier: Option<i64>,
+    pub blight: Option<String>,
+    pub item_level: Option<i64>,
+    pub influence: Option<String>,
+    pub passives: Option<i64>,
+    #[ts(type = "Array<number | null> | null")]
+    pub sparkline: Option<sqlx::types::Json<Vec<Option<f64>>>>,
+    pub total_change: Option<f64>,
+    pub pay_price: Option<f64>,
+    pub listing_count: Option<i64>,
+}
+
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PriceRevision {
+    pub id: i64,
+    pub league: String,
+    pub provider: String,
+    #[ts(type = "string")]
+    pub timestamp: chrono::NaiveDateTime,
+    /// Where imported prices came from, `None` for prices fetched from the provider.
+    pub source: Option<String>,
+}
+
+/// App-wide settings, stored as the single row of the `settings` table.
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct Settings {
+    /// Minutes between background price refreshes, 0 turns them off.
+    pub price_refresh_minutes: i64,
+    /// Timeout for a single request to poe.ninja or the PoE API.
+    pub http_timeout_seconds: i64,
+    /// Days for which every price revision is kept, older ones are thinned out to one a day.
+    pub price_retention_days: i64,
+    pub prune_prices_automatically: bool,
+    /// How far a line may move between two revisions before it is flagged. Either above 1,
+    /// or 0 to turn detection off.
+    pub anomaly_factor: f64,
+}
+
+/// A price line that moved suspiciously far from the revision before it.
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PriceAnomaly {
+    pub id: i64,
+    pub revision: i64,
+    pub previous_revision: i64,
+    pub price_id: i64,
+    pub name: String,
+    pub category: Option<String>,
+    pub variant: Option<String>,
+    pub price: f64,
+    pub previous_price: f64,
+    pub previous_pay_price: Option<f64>,
+    /// Ratio between the larger and the smaller of the two prices.
+    pub factor: f64,
+}
+
+/// Payload of the `price_history_prune_failed` event.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PruneFailed {
+    pub error: String,
+}
+
+/// What pruning the price history removed. Payload of the `price_history_pruned` event.
+#[derive(Debug, Clone, Default, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PruneReport {
+    pub revisions_deleted: i64,
+    pub prices_deleted: i64,
+    pub bytes_reclaimed: i64,
+}
+
+/// Payload of the `prices_updated` event.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PricesUpdated {
+    pub league: String,
+    pub provider: String,
+    pub revision: i64,
+}
+
+/// Payload of the `price_fetch_progress` event, sent whenever a category finished downloading.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PriceFetchProgress {
+    pub league: String,
+    pub provider: String,
+    pub category: String,
+    pub done: i64,
+    pub total: i64,
+}
+
+/// Payload of the `price_fetch_failed` event. `league` and `provider` are `None` when the
+/// refresh failed before it got to fetching any of them.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PriceFetchFailed {
+    pub league: Option<String>,
+    pub provider: Option<String>,
+    pub error: String,
+}
+
+/// Payload of the `scheduled_snapshot_started` event. The `scheduled_snapshot_finished`
+/// event carries the new [`Snapshot`].
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ScheduledSnapshotStarted {
+    pub profile_id: i64,
+}
+
+/// Payload of the `scheduled_snapshot_failed` event. `profile_id` is `None` when finding the
+/// profiles that are due failed.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ScheduledSnapshotFailed {
+    pub profile_id: Option<i64>,
+    pub error: String,
+}
+
+/// A user-set price that takes precedence over the provider's. Overrides without a
+/// `profile_id` apply to every profile.
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct PriceOverride {
+    pub id: i64,
+    pub profile_id: Option<i64>,
+    pub name: String,
+    pub base_type: Option<String>,
+    pub variant: Option<String>,
+    pub price: f64,
+    #[ts(type = "string")]
+    pub timestamp: chrono::NaiveDateTime,
 }
 
 #[derive(Debug, serde::Serialize, TS)]
@@ -96,6 +316,8 @@ pub struct UseEffectResponse {
     pub items: Vec<ItemWithPrice>,
     pub total_chaos: f64,
     pub total_div: f64,
+    pub low_confidence_chaos: f64,
+    pub listed_chaos: f64,
 }
 
 #[derive(Debug, serde::Serialize, TS)]
@@ -103,4 +325,129 @@ pub struct UseEffectResponse {
 pub struct ItemWithPrice {
     pub item: Item,
     pub price: f64,
+    pub change: Option<f64>,
+    pub low_confidence: bool,
+    /// Value at the price in the item's note, if it's listed for sale.
+    pub listed_price: Option<f64>,
+}
+
+#[derive(FromRow, Debug, PartialEq, Eq, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct League {
+    pub id: String,
+    pub realm: Option<String>,
+    #[ts(type = "string | null")]
+    pub start_at: Option<chrono::NaiveDateTime>,
+    #[ts(type = "string | null")]
+    pub end_at: Option<chrono::NaiveDateTime>,
+    pub ninja_indexed: bool,
+    #[ts(type = "string")]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(FromRow, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct Valuation {
+    pub id: i64,
+    pub snapshot_id: i64,
+    pub pricing_revision: i64,
+    #[ts(type = "string")]
+    pub timestamp: chrono::NaiveDateTime,
+    pub value: f64,
+    pub valuation_mode: ValuationMode,
+    pub low_confidence_value: f64,
+    pub listed_value: f64,
+}
+
+#[derive(Debug, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct SnapshotValuation {
+    pub snapshot_id: i64,
+    pub pricing_revision: i64,
+    pub valuation_mode: ValuationMode,
+    pub original_value: f64,
+    pub value: f64,
+    pub low_confidence_value: f64,
+    pub listed_value: f64,
+    pub saved: Option<Valuation>,
+    pub items: Vec<ItemWithPrice>,
+}
+
+/// An item that was gained, lost or whose quantity changed between two snapshots.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ItemChange {
+    pub stash_id: String,
+    pub category: String,
+    pub name: String,
+    pub item: Item,
+    pub quantity_before: i64,
+    pub quantity_after: i64,
+    /// Difference between the stored values of the item in both snapshots.
+    pub chaos_delta: f64,
+}
+
+/// The changes of one category of items in one stash tab.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ItemChangeGroup {
+    pub stash_id: String,
+    pub category: String,
+    pub added: i64,
+    pub removed: i64,
+    pub changed: i64,
+    pub chaos_delta: f64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct SnapshotDiff {
+    pub from_snapshot: i64,
+    pub to_snapshot: i64,
+    pub chaos_delta: f64,
+    pub added: Vec<ItemChange>,
+    pub removed: Vec<ItemChange>,
+    /// Items held in both snapshots, but in different quantities.
+    pub changed: Vec<ItemChange>,
+    pub groups: Vec<ItemChangeGroup>,
+}
+
+/// How one item contributed to the change in value between two snapshots.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ItemAttribution {
+    pub stash_id: String,
+    pub category: String,
+    pub name: String,
+    pub quantity_before: i64,
+    pub quantity_after: i64,
+    /// Chaos value of a single unit at the earlier snapshot's prices.
+    pub unit_price_before: f64,
+    /// Chaos value of a single unit at the later snapshot's prices.
+    pub unit_price_after: f64,
+    /// Zero for items held in only one of the snapshots, see `total`.
+    pub quantity_effect: f64,
+    pub price_effect: f64,
+    pub total: f64,
+}
+
+/// A change in value between two snapshots, split by what caused it.
+///
+/// `quantity_effect` values changed quantities at the earlier prices, `price_effect` values
+/// the later quantities at the change in price, and items held in only one snapshot count
+/// towards `appeared` or `disappeared`. Together they add up to `value_after - value_before`.
+#[derive(Debug, Clone, serde::Serialize, TS)]
+#[ts(export, export_to = "../src/bindings/")]
+pub struct ValueAttribution {
+    pub from_snapshot: i64,
+    pub to_snapshot: i64,
+    pub from_revision: i64,
+    pub to_revision: i64,
+    pub value_before: f64,
+    pub value_after: f64,
+    pub quantity_effect: f64,
+    pub price_effect: f64,
+    pub appeared: f64,
+    pub disappeared: f64,
+    pub items: Vec<ItemAttribution>,
 }
@@ -0,0 +1,134 @@
+use super::model::{Item, PriceOverride};
+use super::pricing::matching;
+use super::{DbCon, Error, Result};
+use sqlx::sqlite::SqlitePool;
+use tauri::State;
+
+/// Every override that applies to `profile_id`.
+pub(super) async fn for_profile(pool: &SqlitePool, profile_id: i64) -> Result<Vec<PriceOverride>> {
+    sqlx::query_as::<_, PriceOverride>(
+        "SELECT * FROM price_overrides WHERE profile_id IS NULL OR profile_id = ?",
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+/// Overrides for `item`, before checking base type and variant.
+pub(super) fn candidates<'a>(
+    overrides: &'a [PriceOverride],
+    item: &'a Item,
+) -> impl Iterator<Item = &'a PriceOverride> {
+    let name = matching::price_name(item);
+    overrides
+        .iter()
+        .filter(move |o| o.name.eq_ignore_ascii_case(name))
+}
+
+fn same(expected: &Option<String>, actual: Option<&str>) -> bool {
+    match expected {
+        Some(expected) => actual.map_or(false, |a| a.eq_ignore_ascii_case(expected)),
+        None => true,
+    }
+}
+
+/// Picks the override to use, preferring profile overrides over global ones and the most
+/// specific override within each. `variant` is the variant of the provider's matching line.
+pub(super) fn best<'a>(
+    overrides: &'a [PriceOverride],
+    item: &'a Item,
+    variant: Option<&str>,
+) -> Option<&'a PriceOverride> {
+    candidates(overrides, item)
+        .filter(|o| same(&o.base_type, Some(&item.base_type)))
+        .filter(|o| same(&o.variant, variant))
+        .max_by_key(|o| {
+            (
+                o.profile_id.is_some(),
+                o.variant.is_some(),
+                o.base_type.is_some(),
+                o.id,
+            )
+        })
+}
+
+#[tauri::command]
+pub(super) async fn get_price_overrides(
+    con: State<'_, DbCon>,
+    profile_id: Option<i64>,
+) -> Result<Vec<PriceOverride>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, PriceOverride>(
+        "SELECT * FROM price_overrides WHERE profile_id IS NULL OR profile_id = ? ORDER BY name, id",
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn create_price_override(
+    con: State<'_, DbCon>,
+    profile_id: Option<i64>,
+    name: String,
+    base_type: Option<String>,
+    variant: Option<String>,
+    price: f64,
+) -> Result<PriceOverride> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, PriceOverride>(
+        "INSERT INTO price_overrides (profile_id, name, base_type, variant, price, timestamp) VALUES (?, ?, ?, ?, ?, ?) RETURNING *",
+    )
+    .bind(profile_id)
+    .bind(name)
+    .bind(base_type)
+    .bind(variant)
+    .bind(price)
+    .bind(chrono::Local::now().naive_local())
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn update_price_override(
+    con: State<'_, DbCon>,
+    price_override: PriceOverride,
+) -> Result<PriceOverride> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, PriceOverride>(
+        "UPDATE price_overrides SET profile_id = ?, name = ?, base_type = ?, variant = ?, price = ?, timestamp = ? WHERE id = ? RETURNING *",
+    )
+    .bind(price_override.profile_id)
+    .bind(price_override.name)
+    .bind(price_override.base_type)
+    .bind(price_override.variant)
+    .bind(price_override.price)
+    .bind(chrono::Local::now().naive_local())
+    .bind(price_override.id)
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn delete_price_override(con: State<'_, DbCon>, id: i64) -> Result<()> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query("DELETE FROM price_overrides WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    Ok(())
+}
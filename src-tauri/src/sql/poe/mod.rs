@@ -0,0 +1,96 @@
+mod ratelimit;
+
+use super::model::Item;
+use super::{Error, Result};
+use crate::oauth::Tokens;
+use reqwest::{Client, StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+pub use ratelimit::RateLimiter;
+
+static API_URL: &str = "https://api.pathofexile.com";
+
+/// Attempts at a request that keeps getting rate limited before it is given up on.
+const MAX_ATTEMPTS: u32 = 4;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct StashTab {
+    pub id: String,
+    pub r#type: String,
+    #[serde(default)]
+    pub children: Vec<StashTab>,
+    pub items: Option<Vec<Item>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StashResponse {
+    stash: StashTab,
+}
+
+/// A client for the PoE API, authorized with the token the user logged in with.
+///
+/// Requests are grouped into endpoints, each with its own rate-limit policy, and wait in
+/// the shared [`RateLimiter`] until they fit within it.
+pub struct PoeClient<'a> {
+    http: Client,
+    token: String,
+    tokens: &'a Tokens,
+    limiter: &'a RateLimiter,
+}
+
+impl<'a> PoeClient<'a> {
+    pub fn new(http: Client, tokens: &'a Tokens, limiter: &'a RateLimiter) -> Result<Self> {
+        Ok(PoeClient {
+            http,
+            token: tokens.access_token().ok_or(Error::NotLoggedIn)?,
+            tokens,
+            limiter,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str, path: &[&str]) -> Result<T> {
+        let mut url = Url::parse(API_URL).unwrap();
+        url.path_segments_mut().unwrap().extend(path);
+
+        let mut attempt = 1;
+        loop {
+            self.limiter.acquire(endpoint).await;
+
+            let resp = self
+                .http
+                .get(url.clone())
+                .bearer_auth(&self.token)
+                .send()
+                .await?;
+            self.limiter.update(endpoint, resp.headers());
+
+            match resp.status() {
+                StatusCode::UNAUTHORIZED => {
+                    self.tokens.set(None);
+                    return Err(Error::NotLoggedIn);
+                }
+                // The limiter holds the next attempt back for as long as Retry-After says
+                StatusCode::TOO_MANY_REQUESTS if attempt < MAX_ATTEMPTS => attempt += 1,
+                _ => return Ok(resp.error_for_status()?.json().await?),
+            }
+        }
+    }
+
+    /// A stash tab with its items. Map tabs only list their sections, see [`Self::stash_section`].
+    pub async fn stash(&self, league: &str, stash_id: &str) -> Result<StashTab> {
+        let resp: StashResponse = self.get("stash", &["stash", league, stash_id]).await?;
+        Ok(resp.stash)
+    }
+
+    pub async fn stash_section(
+        &self,
+        league: &str,
+        stash_id: &str,
+        section_id: &str,
+    ) -> Result<StashTab> {
+        let resp: StashResponse = self
+            .get("stash", &["stash", league, stash_id, section_id])
+            .await?;
+        Ok(resp.stash)
+    }
+}
@@ -0,0 +1,417 @@
+use crate::sql::http;
+use reqwest::header::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// One `hits:period:restriction` entry of a rule: at most `max_hits` requests per `period`,
+/// or requests are refused for `restriction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    pub max_hits: usize,
+    pub period: Duration,
+    pub restriction: Duration,
+}
+
+/// One `hits:period:restricted` entry of a rule's state, as counted by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitState {
+    pub hits: usize,
+    pub period: Duration,
+    /// What is left of an active restriction, zero if there is none.
+    pub restricted: Duration,
+}
+
+/// A rule such as `Account` or `Ip`, with its limits and their current state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rule {
+    pub name: String,
+    pub limits: Vec<Limit>,
+    pub state: Vec<LimitState>,
+}
+
+/// The rate-limit policy a response was counted against, as described by its
+/// `X-Rate-Limit-*` headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok()
+}
+
+/// Parses a comma separated list of `a:b:c` triples, skipping malformed entries.
+fn triples(value: &str) -> Vec<(usize, u64, u64)> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.trim().split(':').map(|p| p.parse::<u64>().ok());
+            Some((parts.next()?? as usize, parts.next()??, parts.next()??))
+        })
+        .collect()
+}
+
+impl Policy {
+    /// Reads the policy from a response, `None` if it doesn't carry any.
+    pub fn parse(headers: &HeaderMap) -> Option<Self> {
+        let name = header(headers, "X-Rate-Limit-Policy")?;
+        let rules = header(headers, "X-Rate-Limit-Rules")?
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let limits = header(headers, &format!("X-Rate-Limit-{}", rule))
+                    .map(triples)
+                    .unwrap_or_default();
+                let state = header(headers, &format!("X-Rate-Limit-{}-State", rule))
+                    .map(triples)
+                    .unwrap_or_default();
+
+                Rule {
+                    name: rule.to_string(),
+                    limits: limits
+                        .into_iter()
+                        .map(|(max_hits, period, restriction)| Limit {
+                            max_hits,
+                            period: Duration::from_secs(period),
+                            restriction: Duration::from_secs(restriction),
+                        })
+                        .collect(),
+                    state: state
+                        .into_iter()
+                        .map(|(hits, period, restricted)| LimitState {
+                            hits,
+                            period: Duration::from_secs(period),
+                            restricted: Duration::from_secs(restricted),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Some(Policy {
+            name: name.to_string(),
+            rules,
+        })
+    }
+
+    fn limits(&self) -> impl Iterator<Item = &Limit> {
+        self.rules.iter().flat_map(|r| r.limits.iter())
+    }
+}
+
+/// What is known about the requests sent under one policy.
+#[derive(Debug, Default)]
+struct Tracker {
+    policy: Option<Policy>,
+    /// When requests were sent, oldest first, as far back as the longest period.
+    sent: VecDeque<Instant>,
+    blocked_until: Option<Instant>,
+}
+
+impl Tracker {
+    fn sent_within(&self, now: Instant, period: Duration) -> usize {
+        self.sent
+            .iter()
+            .rev()
+            .take_while(|t| now.duration_since(**t) < period)
+            .count()
+    }
+
+    /// How long the next request has to wait so that no limit is exceeded.
+    fn wait(&self, now: Instant) -> Duration {
+        let mut until = self.blocked_until.filter(|u| *u > now);
+
+        for limit in self.policy.iter().flat_map(Policy::limits) {
+            let recent = self.sent_within(now, limit.period);
+            if limit.max_hits == 0 || recent < limit.max_hits {
+                continue;
+            }
+
+            // A slot frees up once the request `max_hits` back leaves the period
+            let free = self.sent[self.sent.len() - limit.max_hits] + limit.period;
+            until = until.max(Some(free));
+        }
+
+        until.map_or(Duration::ZERO, |u| u - now)
+    }
+
+    fn block(&mut self, until: Instant) {
+        self.blocked_until = self.blocked_until.max(Some(until));
+    }
+
+    fn update(&mut self, policy: Option<Policy>, retry_after: Option<Duration>, now: Instant) {
+        if let Some(policy) = policy {
+            for state in policy.rules.iter().flat_map(|r| r.state.iter()) {
+                // The server may have counted requests this tracker never saw, e.g. from
+                // before the app was restarted
+                let known = self.sent_within(now, state.period);
+                for _ in known..state.hits {
+                    self.sent.push_back(now);
+                }
+                if !state.restricted.is_zero() {
+                    self.block(now + state.restricted);
+                }
+            }
+            self.policy = Some(policy);
+        }
+
+        if let Some(retry_after) = retry_after {
+            self.block(now + retry_after);
+        }
+
+        let horizon = self
+            .policy
+            .iter()
+            .flat_map(Policy::limits)
+            .map(|l| l.period)
+            .max()
+            .unwrap_or_default();
+        while matches!(self.sent.front(), Some(t) if now.duration_since(*t) >= horizon) {
+            self.sent.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Trackers {
+    /// Which policy each endpoint was last counted against. Endpoints share a tracker
+    /// once they turn out to share a policy.
+    policies: HashMap<String, String>,
+    by_policy: HashMap<String, Tracker>,
+}
+
+impl Trackers {
+    fn tracker(&mut self, endpoint: &str) -> &mut Tracker {
+        let key = self
+            .policies
+            .get(endpoint)
+            .cloned()
+            .unwrap_or_else(|| endpoint.to_string());
+        self.by_policy.entry(key).or_default()
+    }
+}
+
+/// Keeps requests to the PoE API within the limits the API announces, for the whole
+/// lifetime of the app.
+#[derive(Debug, Default)]
+pub struct RateLimiter(Mutex<Trackers>);
+
+impl RateLimiter {
+    /// Waits until a request to `endpoint` can be sent without exceeding any limit and
+    /// counts it as sent.
+    pub async fn acquire(&self, endpoint: &str) {
+        loop {
+            let wait = {
+                let mut trackers = self.0.lock().unwrap();
+                let tracker = trackers.tracker(endpoint);
+                let now = Instant::now();
+                let wait = tracker.wait(now);
+                if wait.is_zero() {
+                    tracker.sent.push_back(now);
+                    return;
+                }
+                wait
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Updates the limits of `endpoint` from the headers of its response.
+    pub fn update(&self, endpoint: &str, headers: &HeaderMap) {
+        let policy = Policy::parse(headers);
+        let retry_after = http::retry_after(headers);
+        let mut trackers = self.0.lock().unwrap();
+
+        if let Some(policy) = &policy {
+            let previous = trackers
+                .policies
+                .insert(endpoint.to_string(), policy.name.clone());
+            // Requests counted before the policy was known belong to it as well
+            if previous.is_none() {
+                if let Some(early) = trackers.by_policy.remove(endpoint) {
+                    let tracker = trackers.by_policy.entry(policy.name.clone()).or_default();
+                    tracker.sent.extend(early.sent);
+                    tracker.sent.make_contiguous().sort();
+                }
+            }
+        }
+
+        trackers
+            .tracker(endpoint)
+            .update(policy, retry_after, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    /// Headers of a stash request, as sent by the PoE API.
+    fn stash_headers(state: &'static str) -> HeaderMap {
+        headers(&[
+            ("X-Rate-Limit-Policy", "stash-request-limit"),
+            ("X-Rate-Limit-Rules", "Account"),
+            ("X-Rate-Limit-Account", "15:10:60,30:300:300"),
+            ("X-Rate-Limit-Account-State", state),
+        ])
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parses_policy_headers() {
+        let policy = Policy::parse(&headers(&[
+            ("X-Rate-Limit-Policy", "backend-item-fetch"),
+            ("X-Rate-Limit-Rules", "Ip,Account"),
+            ("X-Rate-Limit-Ip", "8:10:60,15:60:120"),
+            ("X-Rate-Limit-Ip-State", "1:10:0,1:60:0"),
+            ("X-Rate-Limit-Account", "5:5:60"),
+            ("X-Rate-Limit-Account-State", "5:5:34"),
+        ]))
+        .unwrap();
+
+        assert_eq!(policy.name, "backend-item-fetch");
+        assert_eq!(policy.rules.len(), 2);
+        assert_eq!(policy.rules[0].name, "Ip");
+        assert_eq!(
+            policy.rules[0].limits,
+            vec![
+                Limit {
+                    max_hits: 8,
+                    period: secs(10),
+                    restriction: secs(60),
+                },
+                Limit {
+                    max_hits: 15,
+                    period: secs(60),
+                    restriction: secs(120),
+                },
+            ]
+        );
+        assert_eq!(
+            policy.rules[1].state,
+            vec![LimitState {
+                hits: 5,
+                period: secs(5),
+                restricted: secs(34),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_needs_a_policy() {
+        assert_eq!(Policy::parse(&HeaderMap::new()), None);
+        assert_eq!(
+            Policy::parse(&headers(&[("X-Rate-Limit-Rules", "Ip")])),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_tolerates_missing_rule_headers() {
+        let policy = Policy::parse(&headers(&[
+            ("X-Rate-Limit-Policy", "stash-request-limit"),
+            ("X-Rate-Limit-Rules", "Account, "),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            policy.rules,
+            vec![Rule {
+                name: "Account".to_string(),
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn triples_skip_malformed_entries() {
+        assert_eq!(
+            triples("8:10:60, x:1:2,3:4, 1:2:3"),
+            vec![(8, 10, 60), (1, 2, 3)]
+        );
+        assert_eq!(triples(""), vec![]);
+    }
+
+    #[test]
+    fn waits_for_the_oldest_request_to_leave_the_period() {
+        let start = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.update(Policy::parse(&stash_headers("0:10:0,0:300:0")), None, start);
+
+        assert_eq!(tracker.wait(start), Duration::ZERO);
+
+        for offset in 0..15 {
+            tracker
+                .sent
+                .push_back(start + Duration::from_millis(offset * 100));
+        }
+        // The first of the 15 requests allowed per 10 seconds was sent at `start`
+        assert_eq!(tracker.wait(start + secs(4)), secs(6));
+        assert_eq!(tracker.wait(start + secs(10)), Duration::ZERO);
+    }
+
+    #[test]
+    fn counts_hits_only_the_server_saw() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.update(Policy::parse(&stash_headers("15:10:0,15:300:0")), None, now);
+
+        assert_eq!(tracker.sent.len(), 15);
+        assert_eq!(tracker.wait(now), secs(10));
+
+        // Hits the tracker already knows about aren't counted twice
+        tracker.update(Policy::parse(&stash_headers("15:10:0,15:300:0")), None, now);
+        assert_eq!(tracker.sent.len(), 15);
+    }
+
+    #[test]
+    fn blocks_while_restricted() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.update(
+            Policy::parse(&stash_headers("16:10:60,16:300:0")),
+            None,
+            now,
+        );
+
+        assert_eq!(tracker.wait(now), secs(60));
+        assert_eq!(tracker.wait(now + secs(61)), Duration::ZERO);
+    }
+
+    #[test]
+    fn blocks_for_retry_after() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.update(None, Some(secs(30)), now);
+
+        assert_eq!(tracker.wait(now), secs(30));
+        assert_eq!(tracker.wait(now + secs(10)), secs(20));
+    }
+
+    #[test]
+    fn forgets_requests_older_than_the_longest_period() {
+        let start = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.update(Policy::parse(&stash_headers("1:10:0,1:300:0")), None, start);
+        assert_eq!(tracker.sent.len(), 1);
+
+        tracker.update(None, None, start + secs(300));
+        assert!(tracker.sent.is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::sql::model::{PriceAnomaly, PriceRevision};
+use crate::sql::{Error, Result};
+use sqlx::sqlite::{Sqlite, SqlitePool};
+use sqlx::Transaction;
+
+/// Flags the lines of `revision` whose price moved by more than `factor` in either direction
+/// since `previous`. Lines are compared with the line for the exact same variant, and a
+/// `factor` of 0, or anything else up to 1, turns detection off.
+pub async fn detect(
+    trx: &mut Transaction<'_, Sqlite>,
+    revision: &PriceRevision,
+    previous: &PriceRevision,
+    factor: f64,
+) -> Result<()> {
+    if factor <= 1.0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO price_anomalies (revision, previous_revision, price_id, name, category, variant, price, previous_price, previous_pay_price, factor)
+            SELECT n.revision, o.revision, n.id, n.name, n.category, n.variant, n.price, o.price, o.pay_price, MAX(n.price / o.price, o.price / n.price)
+            FROM price n JOIN price o ON o.league = n.league AND o.revision = ? AND o.name = n.name
+                AND o.category IS n.category AND o.variant IS n.variant AND o.links IS n.links
+                AND o.gem_level IS n.gem_level AND o.gem_quality IS n.gem_quality AND o.gem_quality_type IS n.gem_quality_type
+                AND o.corrupted IS n.corrupted AND o.map_tier IS n.map_tier AND o.blight IS n.blight
+                AND o.item_level IS n.item_level AND o.influence IS n.influence AND o.passives IS n.passives
+            WHERE n.league = ? AND n.revision = ? AND n.price > 0 AND o.price > 0
+                AND MAX(n.price / o.price, o.price / n.price) > ?",
+    )
+    .bind(previous.id)
+    .bind(&revision.league)
+    .bind(revision.id)
+    .bind(factor)
+    .execute(&mut *trx)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(())
+}
+
+pub async fn for_revision(pool: &SqlitePool, revision: i64) -> Result<Vec<PriceAnomaly>> {
+    sqlx::query_as::<_, PriceAnomaly>(
+        "SELECT * FROM price_anomalies WHERE revision = ? ORDER BY factor DESC",
+    )
+    .bind(revision)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)
+}
@@ -0,0 +1,122 @@
+use super::{ninja, PriceLine};
+use crate::sql::{Error, Result};
+use std::path::Path;
+
+/// Splits a CSV record into fields, unquoting `"` quoted fields.
+fn csv_fields(record: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Reads `name,price,league` records, skipping prices for other leagues. A header line and
+/// an empty league column are both allowed.
+fn csv_lines(csv: &str, league: &str, file: &str) -> Result<Vec<PriceLine>> {
+    let mut lines = Vec::new();
+
+    for (i, record) in csv.lines().enumerate() {
+        if record.trim().is_empty() {
+            continue;
+        }
+
+        let fields = csv_fields(record);
+        let (name, price) = match (fields.first(), fields.get(1)) {
+            (Some(name), Some(price)) => (name, price),
+            _ => {
+                return Err(Error::InvalidImport(format!(
+                    "{} line {}: expected name,price,league",
+                    file,
+                    i + 1
+                )))
+            }
+        };
+
+        let price = match price.parse::<f64>() {
+            Ok(price) => price,
+            Err(_) if i == 0 => continue,
+            Err(_) => {
+                return Err(Error::InvalidImport(format!(
+                    "{} line {}: {} is not a price",
+                    file,
+                    i + 1,
+                    price
+                )))
+            }
+        };
+
+        match fields.get(2) {
+            Some(l) if !l.is_empty() && l != league => continue,
+            _ => {}
+        }
+
+        lines.push(PriceLine {
+            name: name.to_string(),
+            price,
+            ..Default::default()
+        });
+    }
+
+    Ok(lines)
+}
+
+/// Reads prices from saved poe.ninja overview responses (`.json`) and `name,price,league`
+/// lists (`.csv`). The responses don't say which category they hold, so it is taken from
+/// `category` or else from the file name, e.g. `UniqueWeapon.json`, and has to be one
+/// poe.ninja knows.
+pub fn read_prices(
+    paths: &[String],
+    league: &str,
+    category: Option<&str>,
+) -> Result<Vec<PriceLine>> {
+    let mut lines = Vec::new();
+
+    for path in paths {
+        let path = Path::new(path);
+        let file = path.display().to_string();
+        let contents = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => {
+                let category = category
+                    .or_else(|| path.file_stem().and_then(|s| s.to_str()))
+                    .unwrap_or_default();
+                // Lines of some categories are matched differently, so a guess won't do
+                if !ninja::is_category(category) {
+                    return Err(Error::InvalidImport(format!(
+                        "{}: {} is not a poe.ninja category, choose the category the file holds",
+                        file, category
+                    )));
+                }
+
+                lines.extend(ninja::parse_overview(&contents, category)?)
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => {
+                lines.extend(csv_lines(&contents, league, &file)?)
+            }
+            _ => {
+                return Err(Error::InvalidImport(format!(
+                    "{}: only .json and .csv files can be imported",
+                    file
+                )))
+            }
+        }
+    }
+
+    Ok(lines)
+}
@@ -0,0 +1,135 @@
+use super::matching::{best_match, price_name};
+use crate::sql::model::{Item, Price, PriceAnomaly, PriceRevision};
+use crate::sql::{Error, Result};
+use sqlx::sqlite::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// How many revisions' prices are kept in memory at once.
+const CACHED_REVISIONS: usize = 3;
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Every price line of one revision, grouped by name so that the variants of an item can be
+/// matched without going back to the database.
+pub struct PriceIndex {
+    pub revision: PriceRevision,
+    by_name: HashMap<String, Vec<Price>>,
+    /// Base and cluster jewel lines by base type, for items without a useful name.
+    by_base: HashMap<String, Vec<Price>>,
+    /// Lines flagged as anomalies, by price id.
+    anomalies: HashMap<i64, PriceAnomaly>,
+}
+
+impl PriceIndex {
+    pub async fn load(pool: &SqlitePool, revision: &PriceRevision) -> Result<Self> {
+        let prices =
+            sqlx::query_as::<_, Price>("SELECT * FROM price WHERE league = ? AND revision = ?")
+                .bind(&revision.league)
+                .bind(revision.id)
+                .fetch_all(pool)
+                .await
+                .map_err(Error::Sql)?;
+
+        let anomalies = super::anomalies::for_revision(pool, revision.id).await?;
+
+        let mut index = PriceIndex {
+            revision: revision.clone(),
+            by_name: HashMap::new(),
+            by_base: HashMap::new(),
+            anomalies: anomalies.into_iter().map(|a| (a.price_id, a)).collect(),
+        };
+
+        for price in prices {
+            let is_base = matches!(
+                price.category.as_deref(),
+                Some("BaseType") | Some("ClusterJewel")
+            );
+            if let (true, Some(base_type)) = (is_base, &price.base_type) {
+                index
+                    .by_base
+                    .entry(normalize(base_type))
+                    .or_default()
+                    .push(price.clone());
+            }
+
+            index
+                .by_name
+                .entry(normalize(&price.name))
+                .or_default()
+                .push(price);
+        }
+
+        Ok(index)
+    }
+
+    /// Any price line stored under `name`, for things that come in a single variant.
+    pub fn get(&self, name: &str) -> Option<&Price> {
+        self.by_name.get(&normalize(name))?.first()
+    }
+
+    /// Why the line with the given id is suspect, if it was flagged when it was stored.
+    pub fn anomaly(&self, price_id: i64) -> Option<&PriceAnomaly> {
+        self.anomalies.get(&price_id)
+    }
+
+    /// The price line that describes `item` best, see [`best_match`].
+    pub fn lookup(&self, item: &Item) -> Option<&Price> {
+        let by_name = self
+            .by_name
+            .get(&normalize(price_name(item)))
+            .and_then(|candidates| best_match(item, candidates));
+        if by_name.is_some() {
+            return by_name;
+        }
+
+        // Rares and white bases have no useful name, so fall back to pricing the base itself
+        if !(0..=2).contains(&item.frame_type) {
+            return None;
+        }
+
+        self.by_base
+            .get(&normalize(&item.base_type))
+            .and_then(|candidates| best_match(item, candidates))
+    }
+}
+
+/// Recently used price indexes. Revisions never change once stored, so they stay valid for as
+/// long as the database they were loaded from is in use.
+#[derive(Default)]
+pub struct PriceCache(Mutex<Vec<Arc<PriceIndex>>>);
+
+impl PriceCache {
+    pub async fn get(
+        &self,
+        pool: &SqlitePool,
+        revision: &PriceRevision,
+    ) -> Result<Arc<PriceIndex>> {
+        let cached = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|i| i.revision.id == revision.id)
+            .cloned();
+        if let Some(index) = cached {
+            return Ok(index);
+        }
+
+        let index = Arc::new(PriceIndex::load(pool, revision).await?);
+
+        let mut cache = self.0.lock().unwrap();
+        cache.retain(|i| i.revision.id != revision.id);
+        cache.insert(0, index.clone());
+        cache.truncate(CACHED_REVISIONS);
+
+        Ok(index)
+    }
+
+    /// Drops every cached index, for when the database is replaced and revision ids start over.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
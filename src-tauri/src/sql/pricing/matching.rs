@@ -0,0 +1,655 @@
+use crate::sql::model::{Item, Price};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The item properties poe.ninja splits its price lines by.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ItemVariant {
+    pub links: i64,
+    pub gem_level: Option<i64>,
+    pub gem_quality: Option<i64>,
+    pub gem_quality_type: Option<String>,
+    pub corrupted: bool,
+    pub map_tier: Option<i64>,
+    pub blight: Option<String>,
+    pub item_level: Option<i64>,
+    pub influences: Vec<String>,
+    pub cluster_passives: Option<i64>,
+    /// The poe.ninja name of the small passives a cluster jewel adds.
+    pub cluster_skill: Option<&'static str>,
+}
+
+impl ItemVariant {
+    pub fn of(item: &Item) -> Self {
+        let is_gem = item.frame_type == 4;
+
+        ItemVariant {
+            links: largest_link_group(item),
+            gem_level: if is_gem {
+                property_value(item, "Level")
+            } else {
+                None
+            },
+            gem_quality: if is_gem {
+                Some(property_value(item, "Quality").unwrap_or(0))
+            } else {
+                None
+            },
+            gem_quality_type: if is_gem {
+                Some(gem_quality_type(&item.type_line).to_string())
+            } else {
+                None
+            },
+            corrupted: item.corrupted.unwrap_or(false),
+            map_tier: property_value(item, "Map Tier"),
+            blight: BLIGHT_PREFIXES
+                .into_iter()
+                .find(|p| item.type_line.contains(p) || item.base_type.contains(p))
+                .map(str::to_string),
+            item_level: item.item_level,
+            influences: influence_set(
+                item.influences
+                    .iter()
+                    .flatten()
+                    .filter(|(_, &v)| v)
+                    .map(|(k, _)| k.as_str()),
+            ),
+            cluster_passives: item
+                .enchant_mods
+                .iter()
+                .flatten()
+                .find_map(|m| m.strip_prefix("Adds ").and_then(leading_number)),
+            cluster_skill: item.enchant_mods.iter().flatten().find_map(|m| {
+                m.strip_prefix("Added Small Passive Skills grant: ")
+                    .and_then(cluster_skill)
+            }),
+        }
+    }
+
+    fn accepts(&self, price: &Price) -> bool {
+        let links = if self.links >= 5 { self.links } else { 0 };
+
+        price.links.unwrap_or(0) <= links
+            && price
+                .gem_level
+                .map_or(true, |l| self.gem_level.map_or(false, |g| l <= g))
+            && price
+                .gem_quality
+                .map_or(true, |q| self.gem_quality.map_or(false, |g| q <= g))
+            && price
+                .gem_quality_type
+                .as_ref()
+                .map_or(true, |t| self.gem_quality_type.as_ref() == Some(t))
+            && price.corrupted.map_or(true, |c| c == self.corrupted)
+            && price.map_tier.map_or(true, |t| {
+                self.map_tier == Some(t) && price.blight == self.blight
+            })
+            && price
+                .item_level
+                .map_or(true, |l| self.item_level.map_or(false, |i| l <= i))
+            && match price.category.as_deref() {
+                Some("BaseType") => {
+                    influence_set(price.influence.iter().flat_map(|i| i.split('/')))
+                        == self.influences
+                }
+                Some("ClusterJewel") => {
+                    price.passives == self.cluster_passives
+                        && self
+                            .cluster_skill
+                            .map_or(false, |s| s.eq_ignore_ascii_case(&price.name))
+                }
+                _ => true,
+            }
+    }
+}
+
+/// Closer matches rank higher: a 6-link prefers the 6-link line over the unlinked one,
+/// a level 21 gem the level 21 line over the level 20 one and so on.
+fn rank(price: &Price) -> (i64, i64, i64, i64) {
+    (
+        price.links.unwrap_or(0),
+        price.gem_level.unwrap_or(0),
+        price.gem_quality.unwrap_or(0),
+        price.item_level.unwrap_or(0),
+    )
+}
+
+/// poe.ninja's names for cluster jewel small passives, with phrases of the enchant that
+/// grants them. Where one enchant's phrases are contained in another's the more specific
+/// one comes first, e.g. `Bow Damage` also grants damage over time.
+static CLUSTER_SKILLS: [(&str, &[&str]); 50] = [
+    ("Axe and Sword Damage", &["axe attacks", "sword attacks"]),
+    (
+        "Mace and Staff Damage",
+        &["mace or sceptre attacks", "staff attacks"],
+    ),
+    (
+        "Dagger and Claw Damage",
+        &["dagger attacks", "claw attacks"],
+    ),
+    ("Bow Damage", &["damage with bows"]),
+    ("Wand Damage", &["wand attacks"]),
+    (
+        "Damage with Two Handed Melee Weapons",
+        &["two handed weapons"],
+    ),
+    (
+        "Attack Damage while Dual Wielding",
+        &["attack damage while dual wielding"],
+    ),
+    (
+        "Attack Damage while holding a Shield",
+        &["attack damage while holding a shield"],
+    ),
+    (
+        "Minion Damage while you have a Herald",
+        &["minions deal", "herald"],
+    ),
+    ("Damage while you have a Herald", &["herald"]),
+    ("Fire Damage over Time Multiplier", &["burning damage"]),
+    (
+        "Chaos Damage over Time Multiplier",
+        &["chaos damage over time"],
+    ),
+    (
+        "Physical Damage over Time Multiplier",
+        &["physical damage over time"],
+    ),
+    (
+        "Cold Damage over Time Multiplier",
+        &["cold damage over time"],
+    ),
+    ("Damage over Time Multiplier", &["damage over time"]),
+    ("Attack Damage", &["increased attack damage"]),
+    ("Spell Damage", &["increased spell damage"]),
+    ("Elemental Damage", &["increased elemental damage"]),
+    ("Physical Damage", &["increased physical damage"]),
+    ("Fire Damage", &["increased fire damage"]),
+    ("Lightning Damage", &["increased lightning damage"]),
+    ("Cold Damage", &["increased cold damage"]),
+    ("Chaos Damage", &["increased chaos damage"]),
+    ("Minion Damage", &["minions deal", "increased damage"]),
+    ("Minion Life", &["minions have", "maximum life"]),
+    (
+        "Effect of Non-Damaging Ailments",
+        &["non-damaging ailments"],
+    ),
+    ("Aura Effect", &["non-curse auras"]),
+    ("Curse Effect", &["effect of your curses"]),
+    ("Exerted Attack Damage", &["exerted attacks"]),
+    ("Critical Chance", &["critical strike chance"]),
+    ("Area Damage", &["increased area damage"]),
+    ("Projectile Damage", &["increased projectile damage"]),
+    ("Trap and Mine Damage", &["trap damage", "mine damage"]),
+    ("Totem Damage", &["totem damage"]),
+    ("Brand Damage", &["brand damage"]),
+    ("Channelling Skill Damage", &["channelling skills"]),
+    ("Flask Duration", &["flask effect duration"]),
+    (
+        "Life and Mana recovery from Flasks",
+        &["life recovery from flasks", "mana recovery from flasks"],
+    ),
+    ("Maximum Life", &["increased maximum life"]),
+    ("Maximum Mana", &["increased maximum mana"]),
+    (
+        "Maximum Energy Shield",
+        &["increased maximum energy shield"],
+    ),
+    ("Armour", &["increased armour"]),
+    ("Evasion", &["increased evasion rating"]),
+    ("Chance to Block Attack Damage", &["block attack damage"]),
+    ("Chance to Block Spell Damage", &["block spell damage"]),
+    ("Elemental Resistance", &["all elemental resistances"]),
+    ("Fire Resistance", &["fire resistance"]),
+    ("Cold Resistance", &["cold resistance"]),
+    ("Lightning Resistance", &["lightning resistance"]),
+    ("Chaos Resistance", &["chaos resistance"]),
+];
+
+/// Resolves the enchant of a cluster jewel, without its `Added Small Passive Skills grant:`
+/// prefix, to the name poe.ninja lists it under.
+pub fn cluster_skill(grant: &str) -> Option<&'static str> {
+    let grant = grant.to_lowercase();
+    CLUSTER_SKILLS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|p| grant.contains(p)))
+        .map(|(name, _)| *name)
+}
+
+static BLIGHT_PREFIXES: [&str; 2] = ["Blight-ravaged", "Blighted"];
+
+/// Splits e.g. `"Blighted Strand Map"` into `("Strand Map", Some("Blighted"))`.
+pub fn split_blight(name: &str) -> (&str, Option<&'static str>) {
+    BLIGHT_PREFIXES
+        .into_iter()
+        .find_map(|prefix| {
+            name.strip_prefix(prefix)
+                .and_then(|n| n.strip_prefix(' '))
+                .map(|base| (base, Some(prefix)))
+        })
+        .unwrap_or((name, None))
+}
+
+pub fn is_map(item: &Item) -> bool {
+    item.frame_type != 3 && property_value(item, "Map Tier").is_some()
+}
+
+/// The name an item's price lines are stored under.
+///
+/// Non-unique maps are priced by their base, so rare names, affixes and the
+/// `Superior` prefix of quality maps are dropped.
+pub fn price_name(item: &Item) -> &str {
+    if is_map(item) {
+        split_blight(item.base_type.trim_start_matches("Superior ")).0
+    } else {
+        item_name(item)
+    }
+}
+
+pub fn item_name(item: &Item) -> &str {
+    if !item.name.is_empty() {
+        &item.name
+    } else {
+        &item.type_line
+    }
+}
+
+/// Alternate quality gems carry their quality type as a prefix of the gem name.
+pub fn gem_quality_type(name: &str) -> &'static str {
+    ["Anomalous", "Divergent", "Phantasmal"]
+        .into_iter()
+        .find(|prefix| name.starts_with(&format!("{} ", prefix)))
+        .unwrap_or("Superior")
+}
+
+/// Reads the leading number of a value, e.g. `20` from `"+20%"`, `"20 (Max)"` or `"20 passives"`.
+pub fn leading_number(value: &str) -> Option<i64> {
+    value
+        .trim_start_matches('+')
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+pub fn property_value(item: &Item, name: &str) -> Option<i64> {
+    let property = item.properties.as_ref()?.iter().find(|p| p.name == name)?;
+    let (value, _) = property.values.first()?;
+
+    leading_number(value)
+}
+
+fn influence_set<'a>(influences: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut set: Vec<String> = influences.map(|i| i.trim().to_lowercase()).collect();
+    set.sort();
+    set
+}
+
+fn largest_link_group(item: &Item) -> i64 {
+    let mut groups: HashMap<i64, i64> = HashMap::new();
+
+    for socket in item.sockets.iter().flatten() {
+        *groups.entry(socket.group).or_default() += 1;
+    }
+
+    groups.into_values().max().unwrap_or(0)
+}
+
+/// Picks the price line that describes `item` best, preferring the cheaper line on ties.
+pub fn best_match<'a>(item: &Item, candidates: &'a [Price]) -> Option<&'a Price> {
+    let variant = ItemVariant::of(item);
+
+    candidates
+        .iter()
+        .filter(|p| variant.accepts(p))
+        .max_by(|a, b| {
+            rank(a)
+                .cmp(&rank(b))
+                .then(b.price.partial_cmp(&a.price).unwrap_or(Ordering::Equal))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(json: serde_json::Value) -> Item {
+        let mut item = json!({
+            "verified": false,
+            "w": 1,
+            "h": 1,
+            "icon": "",
+            "name": "",
+            "typeLine": "",
+            "baseType": "",
+            "identified": true,
+            "frameType": 0,
+        });
+        item.as_object_mut()
+            .unwrap()
+            .extend(json.as_object().unwrap().clone());
+        serde_json::from_value(item).unwrap()
+    }
+
+    fn line(name: &str, price: f64) -> Price {
+        Price {
+            id: 0,
+            name: name.to_string(),
+            price,
+            revision: 1,
+            fully_linked: false,
+            timestamp: chrono::NaiveDateTime::default(),
+            league: "Standard".to_string(),
+            provider: "poe.ninja".to_string(),
+            category: None,
+            base_type: None,
+            variant: None,
+            links: None,
+            gem_level: None,
+            gem_quality: None,
+            gem_quality_type: None,
+            corrupted: None,
+            map_tier: None,
+            blight: None,
+            item_level: None,
+            influence: None,
+            passives: None,
+            sparkline: None,
+            total_change: None,
+            pay_price: None,
+            listing_count: None,
+        }
+    }
+
+    fn sockets(groups: &[i64]) -> serde_json::Value {
+        groups.iter().map(|g| json!({ "group": g })).collect()
+    }
+
+    fn properties(properties: &[(&str, &str)]) -> serde_json::Value {
+        properties
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "values": [[value, 0]] }))
+            .collect()
+    }
+
+    fn matched(item: &Item, lines: &[Price]) -> Option<f64> {
+        best_match(item, lines).map(|p| p.price)
+    }
+
+    #[test]
+    fn prefers_the_line_for_the_links_an_item_has() {
+        let lines = [
+            line("Tabula Rasa", 10.0),
+            Price {
+                links: Some(5),
+                ..line("Tabula Rasa", 50.0)
+            },
+            Price {
+                links: Some(6),
+                ..line("Tabula Rasa", 100.0)
+            },
+        ];
+
+        let six_link = item(json!({ "frameType": 3, "sockets": sockets(&[0; 6]) }));
+        let five_link = item(json!({ "frameType": 3, "sockets": sockets(&[0, 0, 0, 0, 0, 1]) }));
+        let unlinked = item(json!({ "frameType": 3, "sockets": sockets(&[0, 1, 2, 3, 4, 5]) }));
+        // Four links are priced like no links at all
+        let four_link = item(json!({ "frameType": 3, "sockets": sockets(&[0, 0, 0, 0, 1, 1]) }));
+
+        assert_eq!(matched(&six_link, &lines), Some(100.0));
+        assert_eq!(matched(&five_link, &lines), Some(50.0));
+        assert_eq!(matched(&unlinked, &lines), Some(10.0));
+        assert_eq!(matched(&four_link, &lines), Some(10.0));
+    }
+
+    #[test]
+    fn matches_gems_by_level_quality_and_corruption() {
+        let gem = |level: i64, quality: i64, corrupted: bool, price: f64| Price {
+            gem_level: Some(level),
+            gem_quality: Some(quality),
+            gem_quality_type: Some("Superior".to_string()),
+            corrupted: Some(corrupted),
+            ..line("Arc", price)
+        };
+        let lines = [
+            gem(1, 0, false, 1.0),
+            gem(20, 20, false, 20.0),
+            gem(20, 20, true, 15.0),
+            gem(21, 20, true, 200.0),
+            gem(21, 23, true, 400.0),
+        ];
+
+        let corrupted = item(json!({
+            "typeLine": "Arc",
+            "frameType": 4,
+            "corrupted": true,
+            "properties": properties(&[("Level", "21 (Max)"), ("Quality", "+20%")]),
+        }));
+        let clean = item(json!({
+            "typeLine": "Arc",
+            "frameType": 4,
+            "properties": properties(&[("Level", "20 (Max)"), ("Quality", "+20%")]),
+        }));
+        let levelled = item(json!({
+            "typeLine": "Arc",
+            "frameType": 4,
+            "properties": properties(&[("Level", "12")]),
+        }));
+
+        assert_eq!(matched(&corrupted, &lines), Some(200.0));
+        assert_eq!(matched(&clean, &lines), Some(20.0));
+        assert_eq!(matched(&levelled, &lines), Some(1.0));
+    }
+
+    #[test]
+    fn keeps_alternate_quality_gems_apart() {
+        let lines = [
+            Price {
+                gem_quality_type: Some("Superior".to_string()),
+                corrupted: Some(false),
+                ..line("Arc", 1.0)
+            },
+            Price {
+                gem_quality_type: Some("Anomalous".to_string()),
+                corrupted: Some(false),
+                ..line("Anomalous Arc", 30.0)
+            },
+        ];
+        let gem = item(json!({ "typeLine": "Anomalous Arc", "frameType": 4 }));
+
+        assert_eq!(matched(&gem, &lines), Some(30.0));
+        assert_eq!(gem_quality_type("Anomalous Arc"), "Anomalous");
+        assert_eq!(gem_quality_type("Arc"), "Superior");
+        assert_eq!(gem_quality_type("Phantasmal"), "Superior");
+    }
+
+    #[test]
+    fn keeps_blighted_and_plain_maps_of_a_tier_apart() {
+        let map = |tier: i64, blight: Option<&str>, price: f64| Price {
+            map_tier: Some(tier),
+            blight: blight.map(str::to_string),
+            ..line("Strand Map", price)
+        };
+        let lines = [
+            map(15, None, 1.0),
+            map(16, None, 2.0),
+            map(16, Some("Blighted"), 5.0),
+            map(16, Some("Blight-ravaged"), 40.0),
+        ];
+
+        let plain = item(json!({
+            "typeLine": "Superior Strand Map",
+            "baseType": "Strand Map",
+            "properties": properties(&[("Map Tier", "16")]),
+        }));
+        let blighted = item(json!({
+            "typeLine": "Blighted Strand Map",
+            "baseType": "Blighted Strand Map",
+            "properties": properties(&[("Map Tier", "16")]),
+        }));
+        let ravaged = item(json!({
+            "name": "Dread Spires",
+            "typeLine": "Blight-ravaged Strand Map",
+            "baseType": "Blight-ravaged Strand Map",
+            "frameType": 2,
+            "properties": properties(&[("Map Tier", "16")]),
+        }));
+
+        assert_eq!(matched(&plain, &lines), Some(2.0));
+        assert_eq!(matched(&blighted, &lines), Some(5.0));
+        assert_eq!(matched(&ravaged, &lines), Some(40.0));
+
+        assert_eq!(price_name(&plain), "Strand Map");
+        assert_eq!(price_name(&ravaged), "Strand Map");
+    }
+
+    #[test]
+    fn matches_influenced_bases_by_item_level() {
+        let base = |level: i64, influence: Option<&str>, price: f64| Price {
+            category: Some("BaseType".to_string()),
+            item_level: Some(level),
+            influence: influence.map(str::to_string),
+            ..line("Vaal Regalia", price)
+        };
+        let lines = [
+            base(86, None, 1.0),
+            base(82, Some("Shaper"), 10.0),
+            base(85, Some("Shaper"), 20.0),
+            base(86, Some("Shaper"), 50.0),
+            base(86, Some("Elder/Shaper"), 300.0),
+            base(87, Some("Shaper"), 80.0),
+        ];
+
+        let shaper = item(json!({
+            "typeLine": "Vaal Regalia",
+            "ilvl": 86,
+            "influences": { "shaper": true },
+        }));
+        let both = item(json!({
+            "typeLine": "Vaal Regalia",
+            "ilvl": 86,
+            "influences": { "shaper": true, "elder": true },
+        }));
+        let plain = item(json!({ "typeLine": "Vaal Regalia", "ilvl": 86 }));
+        let low = item(json!({
+            "typeLine": "Vaal Regalia",
+            "ilvl": 83,
+            "influences": { "shaper": true },
+        }));
+
+        assert_eq!(matched(&shaper, &lines), Some(50.0));
+        assert_eq!(matched(&both, &lines), Some(300.0));
+        assert_eq!(matched(&plain, &lines), Some(1.0));
+        assert_eq!(matched(&low, &lines), Some(10.0));
+    }
+
+    #[test]
+    fn prefers_the_cheaper_of_equal_matches() {
+        let lines = [
+            line("Exalted Orb", 12.0),
+            line("Exalted Orb", 9.0),
+            line("Exalted Orb", 10.0),
+        ];
+
+        assert_eq!(
+            matched(&item(json!({ "typeLine": "Exalted Orb" })), &lines),
+            Some(9.0)
+        );
+    }
+
+    #[test]
+    fn resolves_cluster_enchants() {
+        for (grant, skill) in [
+            (
+                "Axe Attacks deal 12% increased Damage with Hits and Ailments\nSword Attacks deal 12% increased Damage with Hits and Ailments",
+                "Axe and Sword Damage",
+            ),
+            ("12% increased Damage with Bows\n12% increased Damage Over Time with Bow Skills", "Bow Damage"),
+            ("10% increased Damage over Time", "Damage over Time Multiplier"),
+            ("12% increased Chaos Damage over Time", "Chaos Damage over Time Multiplier"),
+            ("12% increased Chaos Damage", "Chaos Damage"),
+            ("Minions deal 10% increased Damage while you are affected by a Herald", "Minion Damage while you have a Herald"),
+            ("10% increased Damage while affected by a Herald", "Damage while you have a Herald"),
+            ("Minions deal 10% increased Damage", "Minion Damage"),
+            ("12% increased Attack Damage while Dual Wielding", "Attack Damage while Dual Wielding"),
+            ("10% increased Attack Damage", "Attack Damage"),
+            ("Minions have 12% increased maximum Life", "Minion Life"),
+            ("4% increased maximum Life", "Maximum Life"),
+            ("+15% to Fire Resistance", "Fire Resistance"),
+        ] {
+            assert_eq!(cluster_skill(grant), Some(skill), "{}", grant);
+        }
+
+        assert_eq!(cluster_skill("Something else entirely"), None);
+    }
+
+    #[test]
+    fn matches_cluster_jewels_by_skill_and_passives() {
+        let cluster = |name: &str, passives: i64, price: f64| Price {
+            category: Some("ClusterJewel".to_string()),
+            passives: Some(passives),
+            item_level: Some(1),
+            ..line(name, price)
+        };
+        let lines = [
+            cluster("Minion Damage", 8, 5.0),
+            cluster("Minion Damage", 12, 1.0),
+            cluster("Minion Damage while you have a Herald", 4, 20.0),
+            cluster("Damage while you have a Herald", 4, 3.0),
+        ];
+
+        let jewel = |enchants: serde_json::Value| {
+            item(json!({ "typeLine": "Cluster Jewel", "ilvl": 84, "enchantMods": enchants }))
+        };
+        let minion = jewel(json!([
+            "Adds 8 Passive Skills",
+            "Added Small Passive Skills grant: Minions deal 10% increased Damage"
+        ]));
+        let herald = jewel(json!([
+            "Adds 4 Passive Skills",
+            "Added Small Passive Skills grant: Minions deal 10% increased Damage while you are affected by a Herald"
+        ]));
+        let unknown = jewel(json!([
+            "Adds 8 Passive Skills",
+            "Added Small Passive Skills grant: 1% increased Something"
+        ]));
+
+        assert_eq!(matched(&minion, &lines), Some(5.0));
+        assert_eq!(matched(&herald, &lines), Some(20.0));
+        assert_eq!(matched(&unknown, &lines), None);
+    }
+
+    #[test]
+    fn reads_leading_numbers() {
+        assert_eq!(leading_number("+20%"), Some(20));
+        assert_eq!(leading_number("20 (Max)"), Some(20));
+        assert_eq!(leading_number("8 passives"), Some(8));
+        assert_eq!(leading_number("Max"), None);
+        assert_eq!(leading_number(""), None);
+    }
+
+    #[test]
+    fn splits_blight_prefixes() {
+        assert_eq!(
+            split_blight("Blighted Strand Map"),
+            ("Strand Map", Some("Blighted"))
+        );
+        assert_eq!(
+            split_blight("Blight-ravaged Strand Map"),
+            ("Strand Map", Some("Blight-ravaged"))
+        );
+        assert_eq!(split_blight("Strand Map"), ("Strand Map", None));
+        assert_eq!(split_blight("Blightedmap"), ("Blightedmap", None));
+    }
+
+    #[test]
+    fn counts_the_largest_link_group() {
+        assert_eq!(largest_link_group(&item(json!({}))), 0);
+        assert_eq!(
+            largest_link_group(&item(json!({ "sockets": sockets(&[0, 0, 1, 1, 1, 2]) }))),
+            3
+        );
+    }
+}
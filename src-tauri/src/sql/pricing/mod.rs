@@ -0,0 +1,604 @@
+pub mod anomalies;
+mod import;
+pub mod index;
+pub mod matching;
+mod ninja;
+pub mod note;
+pub mod retention;
+
+use super::http::Validators;
+use super::model::{
+    Item, Price, PriceAnomaly, PriceFetchProgress, PriceOverride, PriceRevision, Profile,
+    PruneReport, ValuationMode,
+};
+use super::{overrides, settings, DbCon, Error, Result};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use sqlx::sqlite::{Sqlite, SqliteExecutor, SqlitePool};
+use sqlx::{QueryBuilder, Transaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::State;
+
+pub use index::{PriceCache, PriceIndex};
+pub use ninja::NinjaProvider;
+
+pub static DEFAULT_PROVIDER: &str = "poe.ninja";
+
+/// A single price as reported by a provider, before it is written to the `price` table.
+///
+/// The optional fields describe which variant of an item the price is for; see [`matching`].
+#[derive(Debug, Clone, Default)]
+pub struct PriceLine {
+    pub name: String,
+    /// What a seller receives, falling back to `pay_price` when only that side is known.
+    pub price: f64,
+    /// What a buyer pays, only known for currency.
+    pub pay_price: Option<f64>,
+    /// Number of listings the price is based on, used to judge how reliable it is.
+    pub listing_count: Option<i64>,
+    pub category: Option<String>,
+    pub base_type: Option<String>,
+    pub variant: Option<String>,
+    pub links: Option<i64>,
+    pub gem_level: Option<i64>,
+    pub gem_quality: Option<i64>,
+    pub gem_quality_type: Option<String>,
+    pub corrupted: Option<bool>,
+    pub map_tier: Option<i64>,
+    pub blight: Option<String>,
+    pub item_level: Option<i64>,
+    pub influence: Option<String>,
+    pub passives: Option<i64>,
+    /// Relative price change in percent over the last 7 days, one entry per day.
+    pub sparkline: Option<Vec<Option<f64>>>,
+    pub total_change: Option<f64>,
+}
+
+/// Called by providers after each category is downloaded, with the category and how many
+/// of the total are done.
+pub type Progress<'a> = dyn Fn(&str, usize, usize) + Send + Sync + 'a;
+
+/// The prices of one category, as far as the provider downloaded them.
+#[derive(Debug, Clone)]
+pub struct FetchedCategory {
+    pub category: String,
+    /// `None` if the category hasn't changed since the cached validators were stored.
+    pub lines: Option<Vec<PriceLine>>,
+    pub validators: Validators,
+}
+
+/// A source of item prices for a league.
+///
+/// Every fetch is stored as a new revision in the `price` table, tagged with the provider's id.
+/// `cached` holds the validators of the categories in the latest revision, to be sent along
+/// as conditional requests.
+pub trait PriceProvider: Send + Sync {
+    fn id(&self) -> &'static str;
+
+    fn fetch<'a>(
+        &'a self,
+        http: &'a Client,
+        league: &'a str,
+        cached: &'a HashMap<String, Validators>,
+        progress: &'a Progress<'a>,
+    ) -> BoxFuture<'a, Result<Vec<FetchedCategory>>>;
+}
+
+pub fn providers() -> Vec<Box<dyn PriceProvider>> {
+    vec![Box::new(NinjaProvider)]
+}
+
+pub fn provider(id: &str) -> Option<Box<dyn PriceProvider>> {
+    providers().into_iter().find(|p| p.id() == id)
+}
+
+pub fn validate_provider(id: &str) -> Result<()> {
+    if providers().iter().any(|p| p.id() == id) {
+        Ok(())
+    } else {
+        Err(Error::UnknownProvider(id.to_string()))
+    }
+}
+
+/// Rows per `INSERT` when writing a revision, kept well below SQLite's bound parameter limit.
+const INSERT_BATCH_SIZE: usize = 500;
+
+async fn insert_revision(
+    trx: &mut Transaction<'_, Sqlite>,
+    league: &str,
+    provider: &str,
+    source: Option<&str>,
+) -> Result<PriceRevision> {
+    sqlx::query_as::<_, PriceRevision>(
+        "INSERT INTO price_revisions (league, provider, timestamp, source) VALUES (?, ?, ?, ?) RETURNING *",
+    )
+    .bind(league)
+    .bind(provider)
+    .bind(chrono::Local::now().naive_utc())
+    .bind(source)
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(Error::Sql)
+}
+
+async fn insert_lines(
+    trx: &mut Transaction<'_, Sqlite>,
+    revision: &PriceRevision,
+    lines: &[PriceLine],
+) -> Result<()> {
+    for chunk in lines.chunks(INSERT_BATCH_SIZE) {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO price (name, price, revision, fully_linked, timestamp, league, provider, category, base_type, variant, links, gem_level, gem_quality, gem_quality_type, corrupted, map_tier, blight, item_level, influence, passives, sparkline, total_change, pay_price, listing_count) ",
+        );
+
+        query.push_values(chunk, |mut row, line| {
+            row.push_bind(&line.name)
+                .push_bind(line.price)
+                .push_bind(revision.id)
+                .push_bind(line.links == Some(6))
+                .push_bind(revision.timestamp)
+                .push_bind(&revision.league)
+                .push_bind(&revision.provider)
+                .push_bind(&line.category)
+                .push_bind(&line.base_type)
+                .push_bind(&line.variant)
+                .push_bind(line.links)
+                .push_bind(line.gem_level)
+                .push_bind(line.gem_quality)
+                .push_bind(&line.gem_quality_type)
+                .push_bind(line.corrupted)
+                .push_bind(line.map_tier)
+                .push_bind(&line.blight)
+                .push_bind(line.item_level)
+                .push_bind(&line.influence)
+                .push_bind(line.passives)
+                .push_bind(line.sparkline.as_ref().map(sqlx::types::Json))
+                .push_bind(line.total_change)
+                .push_bind(line.pay_price)
+                .push_bind(line.listing_count);
+        });
+
+        query.build().execute(&mut *trx).await.map_err(Error::Sql)?;
+    }
+
+    Ok(())
+}
+
+/// Writes `lines` imported from `source` as a new revision. Everything happens in one
+/// transaction, so a revision only becomes visible once all of its prices are stored.
+///
+/// Imports aren't checked for anomalies, as they usually fill in history from before the
+/// latest revision.
+pub async fn store_revision(
+    pool: &SqlitePool,
+    league: &str,
+    provider: &str,
+    source: &str,
+    lines: &[PriceLine],
+) -> Result<i64> {
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+
+    let revision = insert_revision(&mut trx, league, provider, Some(source)).await?;
+    insert_lines(&mut trx, &revision, lines).await?;
+
+    trx.commit().await.map_err(Error::Sql)?;
+
+    Ok(revision.id)
+}
+
+/// Like [`store_revision`], but categories the provider reported unchanged are copied over
+/// from the latest revision, and the new one is checked for anomalies against it. The cache
+/// validators are updated in the same transaction so they always describe what the latest
+/// revision holds.
+async fn store_fetched(
+    pool: &SqlitePool,
+    league: &str,
+    provider: &str,
+    categories: &[FetchedCategory],
+) -> Result<i64> {
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+    let previous = find_latest_revision(&mut trx, league, provider).await?;
+    let settings = settings::load(&mut trx).await?;
+
+    let revision = insert_revision(&mut trx, league, provider, None).await?;
+
+    for category in categories {
+        match (&category.lines, &previous) {
+            (Some(lines), _) => {
+                insert_lines(&mut trx, &revision, lines).await?;
+
+                sqlx::query(
+                    "INSERT INTO http_cache (league, provider, category, etag, last_modified) VALUES (?, ?, ?, ?, ?) ON CONFLICT(league, provider, category) DO UPDATE SET etag=excluded.etag, last_modified=excluded.last_modified",
+                )
+                .bind(league)
+                .bind(provider)
+                .bind(&category.category)
+                .bind(&category.validators.etag)
+                .bind(&category.validators.last_modified)
+                .execute(&mut trx)
+                .await
+                .map_err(Error::Sql)?;
+            }
+            (None, Some(previous)) => {
+                sqlx::query(
+                    "INSERT INTO price (name, price, revision, fully_linked, timestamp, league, provider, category, base_type, variant, links, gem_level, gem_quality, gem_quality_type, corrupted, map_tier, blight, item_level, influence, passives, sparkline, total_change, pay_price, listing_count) SELECT name, price, ?, fully_linked, ?, league, provider, category, base_type, variant, links, gem_level, gem_quality, gem_quality_type, corrupted, map_tier, blight, item_level, influence, passives, sparkline, total_change, pay_price, listing_count FROM price WHERE revision = ? AND category = ?",
+                )
+                .bind(revision.id)
+                .bind(revision.timestamp)
+                .bind(previous.id)
+                .bind(&category.category)
+                .execute(&mut trx)
+                .await
+                .map_err(Error::Sql)?;
+            }
+            (None, None) => {}
+        }
+    }
+
+    if let Some(previous) = &previous {
+        anomalies::detect(&mut trx, &revision, previous, settings.anomaly_factor).await?;
+    }
+
+    trx.commit().await.map_err(Error::Sql)?;
+
+    Ok(revision.id)
+}
+
+/// Validators of the categories stored in the latest revision of `league` from `provider`.
+async fn cached_validators(
+    pool: &SqlitePool,
+    league: &str,
+    provider: &str,
+) -> Result<HashMap<String, Validators>> {
+    let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+        "SELECT category, etag, last_modified FROM http_cache WHERE league = ? AND provider = ?",
+    )
+    .bind(league)
+    .bind(provider)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(category, etag, last_modified)| {
+            (
+                category,
+                Validators {
+                    etag,
+                    last_modified,
+                },
+            )
+        })
+        .collect())
+}
+
+pub async fn revision(pool: &SqlitePool, id: i64) -> Result<PriceRevision> {
+    sqlx::query_as::<_, PriceRevision>("SELECT * FROM price_revisions WHERE id = ?")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)
+}
+
+/// Whether `league` has a fetched revision from `provider` that is younger than `max_age`.
+pub async fn has_recent_revision(
+    pool: &SqlitePool,
+    league: &str,
+    provider: &str,
+    max_age: chrono::Duration,
+) -> Result<bool> {
+    let latest = sqlx::query_as::<_, PriceRevision>(
+        "SELECT * FROM price_revisions WHERE league = ? AND provider = ? AND source IS NULL ORDER BY timestamp DESC LIMIT 1",
+    )
+    .bind(league)
+    .bind(provider)
+    .fetch_optional(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(latest.map_or(false, |r| {
+        chrono::Utc::now()
+            .naive_utc()
+            .signed_duration_since(r.timestamp)
+            <= max_age
+    }))
+}
+
+/// The latest revision fetched from `provider`. Imported revisions are left out, as they may
+/// hold prices from any point in time and are only used when asked for by id.
+pub async fn find_latest_revision<'e>(
+    executor: impl SqliteExecutor<'e>,
+    league: &str,
+    provider: &str,
+) -> Result<Option<PriceRevision>> {
+    sqlx::query_as::<_, PriceRevision>(
+        "SELECT * FROM price_revisions WHERE league = ? AND provider = ? AND source IS NULL ORDER BY id DESC LIMIT 1",
+    )
+    .bind(league)
+    .bind(provider)
+    .fetch_optional(executor)
+    .await
+    .map_err(Error::Sql)
+}
+
+pub async fn latest_revision(
+    pool: &SqlitePool,
+    league: &str,
+    provider: &str,
+) -> Result<PriceRevision> {
+    find_latest_revision(pool, league, provider)
+        .await?
+        .ok_or_else(|| Error::NoPriceRevision(league.to_string()))
+}
+
+/// Unit price of a matched line under the given valuation mode.
+pub fn unit_price(price: &Price, mode: ValuationMode) -> f64 {
+    match (mode, price.pay_price) {
+        (ValuationMode::Pay, Some(pay)) => pay,
+        (ValuationMode::Mid, Some(pay)) => (price.price + pay) / 2.0,
+        _ => price.price,
+    }
+}
+
+/// The per-profile knobs that decide how a matched price turns into an item's value.
+#[derive(Debug, Clone, Copy)]
+pub struct ValuationSettings {
+    pub profile_id: i64,
+    pub mode: ValuationMode,
+    pub min_listing_count: i64,
+    pub exclude_low_confidence: bool,
+    pub use_previous_on_anomaly: bool,
+}
+
+impl From<&Profile> for ValuationSettings {
+    fn from(profile: &Profile) -> Self {
+        ValuationSettings {
+            profile_id: profile.id,
+            mode: profile.valuation_mode,
+            min_listing_count: profile.min_listing_count,
+            exclude_low_confidence: profile.exclude_low_confidence,
+            use_previous_on_anomaly: profile.use_previous_on_anomaly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ItemValue {
+    /// Chaos value of the whole stack.
+    pub value: f64,
+    pub change: Option<f64>,
+    /// The matched price is based on fewer listings than the profile asks for.
+    pub low_confidence: bool,
+    /// Chaos value of the whole stack at the price in its note.
+    pub listed: Option<f64>,
+}
+
+impl ItemValue {
+    /// Whether the value should be left out of totals and reported separately instead.
+    pub fn excluded(&self, settings: &ValuationSettings) -> bool {
+        self.low_confidence && settings.exclude_low_confidence
+    }
+
+    /// What the item adds to a snapshot's listed value: its asking price if it has one,
+    /// otherwise whatever it adds to the market value.
+    pub fn listed_or_market(&self, settings: &ValuationSettings) -> f64 {
+        match self.listed {
+            Some(listed) => listed,
+            None if self.excluded(settings) => 0.0,
+            None => self.value,
+        }
+    }
+}
+
+/// Everything needed to value the items of a snapshot, loaded once up front.
+pub struct Valuer {
+    pub prices: Arc<PriceIndex>,
+    overrides: Vec<PriceOverride>,
+    pub settings: ValuationSettings,
+}
+
+impl Valuer {
+    pub async fn load(
+        pool: &SqlitePool,
+        cache: &PriceCache,
+        revision: &PriceRevision,
+        settings: ValuationSettings,
+    ) -> Result<Self> {
+        Ok(Valuer {
+            prices: cache.get(pool, revision).await?,
+            overrides: overrides::for_profile(pool, settings.profile_id).await?,
+            settings,
+        })
+    }
+
+    /// Value of a whole stack of `item`, flagged when the matched price is thin.
+    /// Price overrides set by the user win over the provider's price.
+    pub fn value(&self, item: &Item) -> ItemValue {
+        let stack_size = item.stack_size.unwrap_or(1) as f64;
+        let listed = note::listed_value(item, &self.prices, self.settings.mode);
+
+        if matching::item_name(item) == "Chaos Orb" {
+            return ItemValue {
+                value: stack_size,
+                listed,
+                ..Default::default()
+            };
+        }
+
+        let matched = self.prices.lookup(item);
+
+        // Variant overrides can only be told apart by the provider line the item matches
+        let variant = matched.and_then(|p| p.variant.as_deref());
+        if let Some(price_override) = overrides::best(&self.overrides, item, variant) {
+            return ItemValue {
+                value: price_override.price * stack_size,
+                listed,
+                ..Default::default()
+            };
+        }
+
+        // Flagged lines fall back to the previous revision's price if the profile asks for it
+        let previous;
+        let matched = match matched.and_then(|p| self.prices.anomaly(p.id)) {
+            Some(anomaly) if self.settings.use_previous_on_anomaly => {
+                previous = matched.map(|p| Price {
+                    price: anomaly.previous_price,
+                    pay_price: anomaly.previous_pay_price,
+                    ..p.clone()
+                });
+                previous.as_ref()
+            }
+            _ => matched,
+        };
+
+        match matched {
+            Some(price) => ItemValue {
+                value: unit_price(price, self.settings.mode) * stack_size,
+                change: price.total_change,
+                low_confidence: price
+                    .listing_count
+                    .map_or(false, |c| c < self.settings.min_listing_count),
+                listed,
+            },
+            None => ItemValue {
+                listed,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Fetches and stores new prices. Returns `None` without creating a revision when every
+/// category is unchanged since the latest one.
+pub async fn fetch_and_store(
+    pool: &SqlitePool,
+    http: &Client,
+    league: &str,
+    provider: &dyn PriceProvider,
+    on_progress: impl Fn(PriceFetchProgress) + Send + Sync,
+) -> Result<Option<i64>> {
+    let progress = |category: &str, done: usize, total: usize| {
+        on_progress(PriceFetchProgress {
+            league: league.to_string(),
+            provider: provider.id().to_string(),
+            category: category.to_string(),
+            done: done as i64,
+            total: total as i64,
+        })
+    };
+
+    let cached = match find_latest_revision(pool, league, provider.id()).await? {
+        Some(_) => cached_validators(pool, league, provider.id()).await?,
+        None => HashMap::new(),
+    };
+
+    let categories = provider.fetch(http, league, &cached, &progress).await?;
+    if categories.iter().all(|c| c.lines.is_none()) {
+        return Ok(None);
+    }
+
+    store_fetched(pool, league, provider.id(), &categories)
+        .await
+        .map(Some)
+}
+
+#[tauri::command]
+pub(super) async fn get_price_providers() -> Result<Vec<String>> {
+    Ok(providers().iter().map(|p| p.id().to_string()).collect())
+}
+
+#[tauri::command]
+pub(super) async fn get_price_history(
+    con: State<'_, DbCon>,
+    name: String,
+    league: String,
+    provider: Option<String>,
+) -> Result<Vec<Price>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, Price>(
+        "SELECT * FROM price WHERE name LIKE ? AND league = ? AND provider = ? ORDER BY revision, id",
+    )
+    .bind(name)
+    .bind(league)
+    .bind(provider.unwrap_or_else(|| DEFAULT_PROVIDER.to_string()))
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn get_price_revisions(
+    con: State<'_, DbCon>,
+    league: String,
+    provider: Option<String>,
+) -> Result<Vec<PriceRevision>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, PriceRevision>(
+        "SELECT * FROM price_revisions WHERE league = ? AND provider = ? ORDER BY id DESC",
+    )
+    .bind(league)
+    .bind(provider.unwrap_or_else(|| DEFAULT_PROVIDER.to_string()))
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+/// Stores prices read from local files as a new revision of `league`, e.g. to fill in
+/// history from saved poe.ninja responses. Imports count as `provider`'s prices, poe.ninja
+/// unless given, and record the files they came from. They are never picked as the latest
+/// revision, only used where their id is passed in.
+#[tauri::command]
+pub(super) async fn import_prices(
+    con: State<'_, DbCon>,
+    paths: Vec<String>,
+    league: String,
+    provider: Option<String>,
+    category: Option<String>,
+) -> Result<PriceRevision> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    let provider = provider.unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+    validate_provider(&provider)?;
+
+    let lines = import::read_prices(&paths, &league, category.as_deref())?;
+    if lines.is_empty() {
+        return Err(Error::InvalidImport(format!(
+            "no prices for {} found in {}",
+            league,
+            paths.join(", ")
+        )));
+    }
+
+    let source = format!("file:{}", paths.join(";"));
+    let id = store_revision(pool, &league, &provider, &source, &lines).await?;
+
+    revision(pool, id).await
+}
+
+/// Lines of `revision` that were flagged as anomalies, the largest moves first.
+#[tauri::command]
+pub(super) async fn get_price_anomalies(
+    con: State<'_, DbCon>,
+    revision: i64,
+) -> Result<Vec<PriceAnomaly>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    anomalies::for_revision(pool, revision).await
+}
+
+#[tauri::command]
+pub(super) async fn prune_price_history(con: State<'_, DbCon>) -> Result<PruneReport> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    let settings = settings::load(pool).await?;
+    retention::prune(pool, settings.price_retention_days, true).await
+}
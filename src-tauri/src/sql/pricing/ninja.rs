@@ -0,0 +1,361 @@
+use super::matching::{gem_quality_type, leading_number, split_blight};
+use super::{FetchedCategory, PriceLine, PriceProvider, Progress};
+use crate::sql::http::{self, Validators};
+use crate::sql::Result;
+use futures::future::{BoxFuture, FutureExt, TryFutureExt};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sparkline {
+    data: Vec<Option<f64>>,
+    total_change: f64,
+}
+
+/// poe.ninja only fills in `sparkline` for well-traded items and uses a separate
+/// low-confidence sparkline for the rest.
+fn pick_sparkline(
+    sparkline: Option<Sparkline>,
+    low_confidence: Option<Sparkline>,
+) -> Option<Sparkline> {
+    sparkline
+        .filter(|s| !s.data.is_empty())
+        .or(low_confidence)
+        .filter(|s| !s.data.is_empty())
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemLine {
+    name: String,
+    base_type: Option<String>,
+    variant: Option<String>,
+    chaos_value: f64,
+    links: Option<i64>,
+    gem_level: Option<i64>,
+    gem_quality: Option<i64>,
+    corrupted: Option<bool>,
+    map_tier: Option<i64>,
+    level_required: Option<i64>,
+    count: Option<i64>,
+    listing_count: Option<i64>,
+    sparkline: Option<Sparkline>,
+    low_confidence_sparkline: Option<Sparkline>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NinjaItemResponse {
+    lines: Vec<ItemLine>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CurrencyExchange {
+    value: f64,
+    count: Option<i64>,
+    listing_count: Option<i64>,
+}
+
+impl CurrencyExchange {
+    fn listings(&self) -> Option<i64> {
+        self.listing_count.or(self.count)
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurrencyLine {
+    currency_type_name: String,
+    pay: Option<CurrencyExchange>,
+    receive: Option<CurrencyExchange>,
+    pay_spark_line: Option<Sparkline>,
+    receive_spark_line: Option<Sparkline>,
+    low_confidence_pay_spark_line: Option<Sparkline>,
+    low_confidence_receive_spark_line: Option<Sparkline>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NinjaCurrencyResponse {
+    lines: Vec<CurrencyLine>,
+}
+
+static CURRENCY_CATEGORIES: [&str; 2] = ["Currency", "Fragment"];
+static ITEM_CATEGORIES: [&str; 24] = [
+    "DivinationCard",
+    "Artifact",
+    "Oil",
+    "Incubator",
+    "UniqueWeapon",
+    "UniqueArmour",
+    "UniqueAccessory",
+    "UniqueFlask",
+    "UniqueJewel",
+    "UniqueMap",
+    "DeliriumOrb",
+    "Invitation",
+    "Scarab",
+    "Fossil",
+    "Resonator",
+    "Beast",
+    "Essence",
+    "Vial",
+    "SkillGem",
+    "Map",
+    "BlightedMap",
+    "BlightRavagedMap",
+    "BaseType",
+    "ClusterJewel",
+];
+
+/// Whether `name` is one of the overview categories poe.ninja prices.
+pub fn is_category(name: &str) -> bool {
+    CURRENCY_CATEGORIES.contains(&name) || ITEM_CATEGORIES.contains(&name)
+}
+
+fn currency_lines(resp: NinjaCurrencyResponse, currency_type: &str) -> Vec<PriceLine> {
+    let mut lines = Vec::with_capacity(resp.lines.len());
+
+    for line in resp.lines.into_iter() {
+        // The pay side is quoted as units of the currency per chaos
+        let pay_price = line
+            .pay
+            .as_ref()
+            .map(|p| p.value)
+            .filter(|v| *v > 0.0)
+            .map(|v| 1.0 / v);
+        let listing_count = line
+            .receive
+            .as_ref()
+            .and_then(CurrencyExchange::listings)
+            .or(line.pay.as_ref().and_then(CurrencyExchange::listings));
+        let price = match line.receive.map(|r| r.value).or(pay_price) {
+            Some(price) => price,
+            None => continue,
+        };
+        let sparkline = pick_sparkline(
+            line.receive_spark_line,
+            line.low_confidence_receive_spark_line,
+        )
+        .or(pick_sparkline(
+            line.pay_spark_line,
+            line.low_confidence_pay_spark_line,
+        ));
+
+        lines.push(PriceLine {
+            name: line.currency_type_name,
+            price,
+            pay_price,
+            listing_count,
+            category: Some(currency_type.to_string()),
+            total_change: sparkline.as_ref().map(|s| s.total_change),
+            sparkline: sparkline.map(|s| s.data),
+            ..Default::default()
+        });
+    }
+
+    lines
+}
+
+fn item_lines(resp: NinjaItemResponse, item_type: &str) -> Vec<PriceLine> {
+    let mut lines = Vec::with_capacity(resp.lines.len());
+
+    for line in resp.lines.into_iter() {
+        let is_gem = item_type == "SkillGem";
+        // Base and cluster jewel lines use `levelRequired` for the item level and
+        // `variant` for the influence or the number of passives respectively
+        let is_base = item_type == "BaseType";
+        let is_cluster = item_type == "ClusterJewel";
+        // Blighted map lines are stored under their base map so they share a lookup name
+        let (name, blight) = split_blight(&line.name);
+        let sparkline = pick_sparkline(line.sparkline, line.low_confidence_sparkline);
+
+        lines.push(PriceLine {
+            name: name.to_string(),
+            price: line.chaos_value,
+            pay_price: None,
+            listing_count: line.listing_count.or(line.count),
+            category: Some(item_type.to_string()),
+            base_type: line.base_type,
+            links: line.links,
+            gem_level: line.gem_level,
+            gem_quality: line.gem_quality,
+            gem_quality_type: if is_gem {
+                Some(gem_quality_type(&line.name).to_string())
+            } else {
+                None
+            },
+            // Gem lines only carry the flag when it's set, but corruption matters for gems
+            corrupted: if is_gem {
+                Some(line.corrupted.unwrap_or(false))
+            } else {
+                line.corrupted
+            },
+            map_tier: line.map_tier,
+            blight: blight.map(str::to_string),
+            item_level: if is_base || is_cluster {
+                line.level_required
+            } else {
+                None
+            },
+            influence: if is_base { line.variant.clone() } else { None },
+            passives: if is_cluster {
+                line.variant.as_deref().and_then(leading_number)
+            } else {
+                None
+            },
+            variant: line.variant,
+            total_change: sparkline.as_ref().map(|s| s.total_change),
+            sparkline: sparkline.map(|s| s.data),
+        });
+    }
+
+    lines
+}
+
+/// Either shape of poe.ninja overview response, as found in saved copies of the API.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum NinjaResponse {
+    Currency(NinjaCurrencyResponse),
+    Item(NinjaItemResponse),
+}
+
+/// Parses a saved poe.ninja overview response for `category`.
+pub fn parse_overview(json: &str, category: &str) -> serde_json::Result<Vec<PriceLine>> {
+    Ok(match serde_json::from_str(json)? {
+        NinjaResponse::Currency(resp) => currency_lines(resp, category),
+        NinjaResponse::Item(resp) => item_lines(resp, category),
+    })
+}
+
+pub struct NinjaProvider;
+
+/// How many category downloads run at the same time.
+const CONCURRENT_REQUESTS: usize = 6;
+
+/// Sends a category request, conditional on `cached` if given. Returns `None` when poe.ninja
+/// answers that the category hasn't changed.
+async fn fetch_json<T: serde::de::DeserializeOwned>(
+    request: RequestBuilder,
+    cached: Option<&Validators>,
+) -> Result<Option<(T, Validators)>> {
+    let request = match cached {
+        Some(cached) => cached.apply(request),
+        None => request,
+    };
+
+    let resp = http::send(request).await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let resp = resp.error_for_status()?;
+    let validators = Validators::of(&resp);
+
+    Ok(Some((resp.json().await?, validators)))
+}
+
+fn unchanged(category: &str) -> FetchedCategory {
+    FetchedCategory {
+        category: category.to_string(),
+        lines: None,
+        validators: Validators::default(),
+    }
+}
+
+async fn fetch_currency(
+    http: &Client,
+    league: &str,
+    currency_type: &'static str,
+    cached: Option<&Validators>,
+) -> Result<FetchedCategory> {
+    let request = http
+        .get("https://poe.ninja/api/data/currencyoverview")
+        .query(&[("league", league), ("type", currency_type)]);
+    let (resp, validators) = match fetch_json::<NinjaCurrencyResponse>(request, cached).await? {
+        Some(fetched) => fetched,
+        None => return Ok(unchanged(currency_type)),
+    };
+
+    Ok(FetchedCategory {
+        category: currency_type.to_string(),
+        lines: Some(currency_lines(resp, currency_type)),
+        validators,
+    })
+}
+
+async fn fetch_items(
+    http: &Client,
+    league: &str,
+    item_type: &'static str,
+    cached: Option<&Validators>,
+) -> Result<FetchedCategory> {
+    let request = http
+        .get("https://poe.ninja/api/data/itemoverview")
+        .query(&[("league", league), ("type", item_type)]);
+    let (resp, validators) = match fetch_json::<NinjaItemResponse>(request, cached).await? {
+        Some(fetched) => fetched,
+        None => return Ok(unchanged(item_type)),
+    };
+
+    Ok(FetchedCategory {
+        category: item_type.to_string(),
+        lines: Some(item_lines(resp, item_type)),
+        validators,
+    })
+}
+
+impl NinjaProvider {
+    async fn fetch_lines(
+        &self,
+        http: &Client,
+        league: &str,
+        cached: &HashMap<String, Validators>,
+        progress: &Progress<'_>,
+    ) -> Result<Vec<FetchedCategory>> {
+        let total = CURRENCY_CATEGORIES.len() + ITEM_CATEGORIES.len();
+        let done = AtomicUsize::new(0);
+        let report = |category: &'static str| {
+            progress(category, done.fetch_add(1, Ordering::SeqCst) + 1, total);
+        };
+
+        let mut fetches: Vec<BoxFuture<Result<FetchedCategory>>> = Vec::with_capacity(total);
+        for currency_type in CURRENCY_CATEGORIES {
+            fetches.push(
+                fetch_currency(http, league, currency_type, cached.get(currency_type))
+                    .inspect_ok(move |_| report(currency_type))
+                    .boxed(),
+            );
+        }
+        for item_type in ITEM_CATEGORIES {
+            fetches.push(
+                fetch_items(http, league, item_type, cached.get(item_type))
+                    .inspect_ok(move |_| report(item_type))
+                    .boxed(),
+            );
+        }
+
+        stream::iter(fetches)
+            .buffered(CONCURRENT_REQUESTS)
+            .try_collect()
+            .await
+    }
+}
+
+impl PriceProvider for NinjaProvider {
+    fn id(&self) -> &'static str {
+        "poe.ninja"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        http: &'a Client,
+        league: &'a str,
+        cached: &'a HashMap<String, Validators>,
+        progress: &'a Progress<'a>,
+    ) -> BoxFuture<'a, Result<Vec<FetchedCategory>>> {
+        Box::pin(self.fetch_lines(http, league, cached, progress))
+    }
+}
@@ -0,0 +1,134 @@
+use super::index::PriceIndex;
+use super::unit_price;
+use crate::sql::model::{Item, ValuationMode};
+
+/// Shorthands the trade site uses for currencies in price notes.
+static CURRENCY_ALIASES: [(&str, &str); 25] = [
+    ("chaos", "Chaos Orb"),
+    ("c", "Chaos Orb"),
+    ("divine", "Divine Orb"),
+    ("div", "Divine Orb"),
+    ("d", "Divine Orb"),
+    ("exalted", "Exalted Orb"),
+    ("exa", "Exalted Orb"),
+    ("ex", "Exalted Orb"),
+    ("mirror", "Mirror of Kalandra"),
+    ("alch", "Orb of Alchemy"),
+    ("alt", "Orb of Alteration"),
+    ("fusing", "Orb of Fusing"),
+    ("jewellers", "Jeweller's Orb"),
+    ("chrome", "Chromatic Orb"),
+    ("chance", "Orb of Chance"),
+    ("chisel", "Cartographer's Chisel"),
+    ("scour", "Orb of Scouring"),
+    ("blessed", "Blessed Orb"),
+    ("regret", "Orb of Regret"),
+    ("regal", "Regal Orb"),
+    ("vaal", "Vaal Orb"),
+    ("gcp", "Gemcutter's Prism"),
+    ("annul", "Orb of Annulment"),
+    ("bauble", "Glassblower's Bauble"),
+    ("engineers", "Engineer's Orb"),
+];
+
+/// A buyout or asking price taken from an item note like `~b/o 1/2 divine`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotePrice {
+    pub amount: f64,
+    pub currency: String,
+}
+
+fn parse_amount(amount: &str) -> Option<f64> {
+    let amount = match amount.split_once('/') {
+        Some((num, den)) => num.parse::<f64>().ok()? / den.parse::<f64>().ok()?,
+        None => amount.parse::<f64>().ok()?,
+    };
+
+    Some(amount).filter(|a| a.is_finite() && *a >= 0.0)
+}
+
+pub fn parse(note: &str) -> Option<NotePrice> {
+    let mut parts = note.split_whitespace();
+
+    match parts.next()? {
+        "~b/o" | "~price" => {}
+        _ => return None,
+    }
+
+    let amount = parse_amount(parts.next()?)?;
+    let currency = parts.next()?;
+    let currency = CURRENCY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(currency))
+        .map_or(currency, |(_, name)| name);
+
+    Some(NotePrice {
+        amount,
+        currency: currency.to_string(),
+    })
+}
+
+/// Chaos value of the whole stack at the price listed in the item's note, if it has one.
+/// The listed currency is converted using `prices`, so it's `None` for currencies the
+/// revision has no price for.
+pub fn listed_value(item: &Item, prices: &PriceIndex, mode: ValuationMode) -> Option<f64> {
+    let note_price = item.note.as_deref().and_then(parse)?;
+
+    let currency_price = if note_price.currency == "Chaos Orb" {
+        1.0
+    } else {
+        unit_price(prices.get(&note_price.currency)?, mode)
+    };
+
+    // Notes on stackable items price a single unit
+    Some(currency_price * note_price.amount * item.stack_size.unwrap_or(1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(amount: f64, currency: &str) -> Option<NotePrice> {
+        Some(NotePrice {
+            amount,
+            currency: currency.to_string(),
+        })
+    }
+
+    #[test]
+    fn parses_buyout_and_asking_prices() {
+        assert_eq!(parse("~b/o 5 chaos"), price(5.0, "Chaos Orb"));
+        assert_eq!(parse("~price 5 chaos"), price(5.0, "Chaos Orb"));
+        assert_eq!(parse("~b/o 1.5 divine"), price(1.5, "Divine Orb"));
+    }
+
+    #[test]
+    fn parses_fractional_amounts() {
+        assert_eq!(parse("~b/o 1/2 divine"), price(0.5, "Divine Orb"));
+        assert_eq!(parse("~price 3/4 ex"), price(0.75, "Exalted Orb"));
+        assert_eq!(parse("~b/o 1/0 divine"), None);
+        assert_eq!(parse("~b/o 1/x divine"), None);
+    }
+
+    #[test]
+    fn resolves_currency_aliases() {
+        assert_eq!(parse("~b/o 10 c"), price(10.0, "Chaos Orb"));
+        assert_eq!(parse("~b/o 1 Div"), price(1.0, "Divine Orb"));
+        assert_eq!(parse("~b/o 20 gcp"), price(20.0, "Gemcutter's Prism"));
+        // Currencies without an alias are kept as written
+        assert_eq!(
+            parse("~b/o 3 awakened-sextant"),
+            price(3.0, "awakened-sextant")
+        );
+    }
+
+    #[test]
+    fn ignores_other_notes() {
+        assert_eq!(parse("5 chaos"), None);
+        assert_eq!(parse("~skip"), None);
+        assert_eq!(parse("~b/o"), None);
+        assert_eq!(parse("~b/o 5"), None);
+        assert_eq!(parse("~b/o -1 chaos"), None);
+        assert_eq!(parse(""), None);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::sql::model::PruneReport;
+use crate::sql::{Error, Result};
+use chrono::{Duration, Utc};
+use sqlx::sqlite::SqlitePool;
+
+async fn database_size(pool: &SqlitePool) -> Result<i64> {
+    let (size,) = sqlx::query_as::<_, (i64,)>(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(size)
+}
+
+/// Deletes price revisions that are no longer needed, and with `vacuum` gives the space back.
+///
+/// Kept are revisions younger than `retention_days`, the latest one of every league and
+/// provider, the last one of every day, and any revision a snapshot or saved valuation
+/// was priced at.
+///
+/// `VACUUM` fails while other connections are writing, so only run it when nothing else
+/// is. Without it the freed pages are reused by later writes instead.
+pub async fn prune(pool: &SqlitePool, retention_days: i64, vacuum: bool) -> Result<PruneReport> {
+    let cutoff = Utc::now().naive_utc() - Duration::days(retention_days.max(0));
+    let size_before = database_size(pool).await?;
+
+    let prunable = sqlx::query_as::<_, (i64,)>(
+        "SELECT id FROM price_revisions WHERE timestamp < ?
+            AND id NOT IN (SELECT pricing_revision FROM snapshots)
+            AND id NOT IN (SELECT pricing_revision FROM valuations)
+            AND id NOT IN (SELECT MAX(id) FROM price_revisions WHERE source IS NULL GROUP BY league, provider)
+            AND id NOT IN (SELECT MAX(id) FROM price_revisions GROUP BY league, provider, date(timestamp))",
+    )
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    let mut report = PruneReport::default();
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+
+    for (id,) in prunable {
+        sqlx::query("DELETE FROM price_anomalies WHERE revision = ?")
+            .bind(id)
+            .execute(&mut trx)
+            .await
+            .map_err(Error::Sql)?;
+
+        report.prices_deleted += sqlx::query("DELETE FROM price WHERE revision = ?")
+            .bind(id)
+            .execute(&mut trx)
+            .await
+            .map_err(Error::Sql)?
+            .rows_affected() as i64;
+
+        sqlx::query("DELETE FROM price_revisions WHERE id = ?")
+            .bind(id)
+            .execute(&mut trx)
+            .await
+            .map_err(Error::Sql)?;
+
+        report.revisions_deleted += 1;
+    }
+
+    trx.commit().await.map_err(Error::Sql)?;
+
+    if vacuum && report.revisions_deleted > 0 {
+        sqlx::query("VACUUM")
+            .execute(pool)
+            .await
+            .map_err(Error::Sql)?;
+    }
+
+    report.bytes_reclaimed = (size_before - database_size(pool).await?).max(0);
+
+    Ok(report)
+}
@@ -0,0 +1,236 @@
+use super::http::HttpClient;
+use super::model::{
+    PriceFetchFailed, PricesUpdated, PruneFailed, ScheduledSnapshotFailed, ScheduledSnapshotStarted,
+};
+use super::poe::RateLimiter;
+use super::{leagues, pricing, settings, snapshots, DbCon, Error, Result};
+use crate::oauth::Tokens;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::HashMap;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// How often the scheduler wakes up to check whether anything is due.
+const TICK: std::time::Duration = std::time::Duration::from_secs(60);
+/// How often old prices are pruned when automatic pruning is on.
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Runs background jobs for as long as the app is open.
+pub(super) fn spawn<R: Runtime>(app: AppHandle<R>) {
+    spawn_snapshots(app.clone());
+
+    tauri::async_runtime::spawn(async move {
+        let mut last_checked = HashMap::new();
+        let mut last_pruned: Option<Instant> = None;
+
+        loop {
+            if let Err(e) = refresh_prices(&app, &mut last_checked).await {
+                let _ = app.emit_all(
+                    "price_fetch_failed",
+                    PriceFetchFailed {
+                        league: None,
+                        provider: None,
+                        error: e.to_string(),
+                    },
+                );
+            }
+
+            if last_pruned.map_or(true, |t| t.elapsed() >= PRUNE_INTERVAL) {
+                last_pruned = Some(Instant::now());
+
+                if let Err(e) = prune_prices(&app).await {
+                    let _ = app.emit_all(
+                        "price_history_prune_failed",
+                        PruneFailed {
+                            error: e.to_string(),
+                        },
+                    );
+                }
+            }
+
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+/// Takes scheduled snapshots on a task of their own, as a snapshot can wait on the rate
+/// limiter for minutes and shouldn't hold up price refreshes.
+fn spawn_snapshots<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_attempted = HashMap::new();
+
+        loop {
+            if let Err(e) = take_scheduled_snapshots(&app, &mut last_attempted).await {
+                let _ = app.emit_all(
+                    "scheduled_snapshot_failed",
+                    ScheduledSnapshotFailed {
+                        profile_id: None,
+                        error: e.to_string(),
+                    },
+                );
+            }
+
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+/// Snapshots every profile whose latest snapshot is older than its interval. `last_attempted`
+/// keeps a failing profile from being retried before its next interval, since failed
+/// snapshots don't count as its latest. The retry resumes the failed snapshot.
+async fn take_scheduled_snapshots<R: Runtime>(
+    app: &AppHandle<R>,
+    last_attempted: &mut HashMap<i64, Instant>,
+) -> Result<()> {
+    let pool = match app.state::<DbCon>().db.lock().await.clone() {
+        Some(pool) => pool,
+        None => return Ok(()),
+    };
+
+    // Nothing can be fetched until the user logs in, which isn't worth a failure event
+    let logged_in = app
+        .try_state::<Tokens>()
+        .map_or(false, |tokens| tokens.access_token().is_some());
+    if !logged_in {
+        return Ok(());
+    }
+
+    // Pending snapshots count too, so a snapshot taken by hand isn't doubled up
+    let due = sqlx::query_as::<_, (i64, i64, Option<NaiveDateTime>)>(
+        "SELECT p.id, p.snapshot_interval_minutes, MAX(s.timestamp) FROM profiles p
+            LEFT JOIN snapshots s ON s.profile_id = p.id AND s.status != 'failed'
+            WHERE p.snapshot_interval_minutes > 0 GROUP BY p.id",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    for (profile_id, minutes, latest) in due {
+        let interval = Duration::minutes(snapshots::snapshot_interval(minutes));
+
+        let attempted_recently = last_attempted.get(&profile_id).map_or(false, |t| {
+            t.elapsed() < interval.to_std().unwrap_or_default()
+        });
+        let taken_recently = latest.map_or(false, |t| {
+            chrono::Local::now().naive_local().signed_duration_since(t) < interval
+        });
+        if attempted_recently || taken_recently {
+            continue;
+        }
+
+        last_attempted.insert(profile_id, Instant::now());
+        let _ = app.emit_all(
+            "scheduled_snapshot_started",
+            ScheduledSnapshotStarted { profile_id },
+        );
+
+        let taken = snapshots::take_scheduled_snapshot(
+            &app.state::<DbCon>(),
+            &app.state::<HttpClient>(),
+            &app.state::<Tokens>(),
+            &app.state::<RateLimiter>(),
+            profile_id,
+        )
+        .await;
+
+        let _ = match taken {
+            Ok(snapshot) => app.emit_all("scheduled_snapshot_finished", snapshot),
+            Err(e) => app.emit_all(
+                "scheduled_snapshot_failed",
+                ScheduledSnapshotFailed {
+                    profile_id: Some(profile_id),
+                    error: e.to_string(),
+                },
+            ),
+        };
+    }
+
+    Ok(())
+}
+
+/// Fetches new prices for every league and provider some profile uses, once their latest
+/// revision is older than the configured refresh interval. `last_checked` remembers fetches
+/// that found nothing new, since those don't leave a revision behind.
+async fn refresh_prices<R: Runtime>(
+    app: &AppHandle<R>,
+    last_checked: &mut HashMap<(String, String), Instant>,
+) -> Result<()> {
+    // Clone the pool so commands aren't locked out while prices download
+    let pool = match app.state::<DbCon>().db.lock().await.clone() {
+        Some(pool) => pool,
+        None => return Ok(()),
+    };
+
+    let settings = settings::load(&pool).await?;
+    if settings.price_refresh_minutes == 0 {
+        return Ok(());
+    }
+    let max_age = Duration::minutes(settings.price_refresh_minutes);
+
+    for (league, provider_id) in leagues::used_pricing_sources(&pool).await? {
+        let provider = match pricing::provider(&provider_id) {
+            Some(provider) => provider,
+            None => continue,
+        };
+
+        let key = (league.clone(), provider_id.clone());
+        let checked_recently = last_checked.get(&key).map_or(false, |t| {
+            t.elapsed() < max_age.to_std().unwrap_or_default()
+        });
+        if checked_recently
+            || pricing::has_recent_revision(&pool, &league, &provider_id, max_age).await?
+        {
+            continue;
+        }
+
+        let http = app.state::<HttpClient>().client();
+        let fetched =
+            pricing::fetch_and_store(&pool, &http, &league, provider.as_ref(), |progress| {
+                let _ = app.emit_all("price_fetch_progress", progress);
+            })
+            .await;
+        last_checked.insert(key, Instant::now());
+
+        let _ = match fetched {
+            Ok(None) => continue,
+            Ok(Some(revision)) => app.emit_all(
+                "prices_updated",
+                PricesUpdated {
+                    league,
+                    provider: provider_id,
+                    revision,
+                },
+            ),
+            Err(e) => app.emit_all(
+                "price_fetch_failed",
+                PriceFetchFailed {
+                    league: Some(league),
+                    provider: Some(provider_id),
+                    error: e.to_string(),
+                },
+            ),
+        };
+    }
+
+    Ok(())
+}
+
+async fn prune_prices<R: Runtime>(app: &AppHandle<R>) -> Result<()> {
+    let pool = match app.state::<DbCon>().db.lock().await.clone() {
+        Some(pool) => pool,
+        None => return Ok(()),
+    };
+
+    let settings = settings::load(&pool).await?;
+    if !settings.prune_prices_automatically {
+        return Ok(());
+    }
+
+    // Snapshots and price refreshes may be writing, which `VACUUM` can't run alongside
+    let report = pricing::retention::prune(&pool, settings.price_retention_days, false).await?;
+    if report.revisions_deleted > 0 {
+        let _ = app.emit_all("price_history_pruned", report);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use super::http::HttpClient;
+use super::model::Settings;
+use super::{DbCon, Error, Result};
+use sqlx::sqlite::SqliteExecutor;
+use tauri::State;
+
+pub(super) async fn load<'e>(executor: impl SqliteExecutor<'e>) -> Result<Settings> {
+    sqlx::query_as::<_, Settings>("SELECT * FROM settings WHERE id = 1")
+        .fetch_one(executor)
+        .await
+        .map_err(Error::Sql)
+}
+
+#[tauri::command]
+pub(super) async fn get_settings(con: State<'_, DbCon>) -> Result<Settings> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    load(pool).await
+}
+
+#[tauri::command]
+pub(super) async fn update_settings(
+    con: State<'_, DbCon>,
+    http: State<'_, HttpClient>,
+    settings: Settings,
+) -> Result<Settings> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    // A factor of 1 or less would flag every move, or none
+    let anomaly_factor = settings.anomaly_factor.max(0.0);
+    if anomaly_factor > 0.0 && anomaly_factor <= 1.0 {
+        return Err(Error::InvalidAnomalyFactor(anomaly_factor));
+    }
+
+    let settings = sqlx::query_as::<_, Settings>(
+        "UPDATE settings SET price_refresh_minutes = ?, http_timeout_seconds = ?, price_retention_days = ?, prune_prices_automatically = ?, anomaly_factor = ? WHERE id = 1 RETURNING *",
+    )
+    .bind(settings.price_refresh_minutes.max(0))
+    .bind(settings.http_timeout_seconds.max(1))
+    .bind(settings.price_retention_days.max(0))
+    .bind(settings.prune_prices_automatically)
+    .bind(anomaly_factor)
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    http.configure(settings.http_timeout_seconds)?;
+
+    Ok(settings)
+}
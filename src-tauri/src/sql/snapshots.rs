@@ -0,0 +1,444 @@
+use super::http::HttpClient;
+use super::model::{Item, Profile, Snapshot, SnapshotStatus};
+use super::poe::{PoeClient, RateLimiter};
+use super::pricing::{self, PriceCache, ValuationSettings, Valuer};
+use super::{DbCon, Error, Result};
+use crate::oauth::Tokens;
+use chrono::Duration;
+use sqlx::sqlite::{Sqlite, SqlitePool};
+use sqlx::Transaction;
+use tauri::State;
+
+/// Value totals of the items written into a snapshot.
+#[derive(Debug, Default)]
+pub(super) struct Totals {
+    pub value: f64,
+    pub low_confidence_value: f64,
+    pub listed_value: f64,
+}
+
+/// The items of one tab, or of every section of a map tab.
+pub(super) struct FetchedTab {
+    pub stash_id: String,
+    pub items: Vec<Item>,
+}
+
+/// Values `items` and writes them into `snapshot` as the contents of `stash_id`.
+pub(super) async fn insert_items(
+    trx: &mut Transaction<'_, Sqlite>,
+    snapshot: &Snapshot,
+    stash_id: &str,
+    items: &[Item],
+    valuer: &Valuer,
+) -> Result<Totals> {
+    let settings = &valuer.settings;
+    let mut totals = Totals::default();
+
+    for item in items {
+        let value = valuer.value(item);
+
+        if value.excluded(settings) {
+            totals.low_confidence_value += value.value;
+        } else {
+            totals.value += value.value;
+        }
+        totals.listed_value += value.listed_or_market(settings);
+
+        sqlx::query(
+            "INSERT INTO item (snapshot_id, stash_id, data, value, price_change, low_confidence, listed_value) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(snapshot.id)
+        .bind(stash_id)
+        .bind(sqlx::types::Json(item))
+        .bind(value.value)
+        .bind(value.change)
+        .bind(value.low_confidence)
+        .bind(value.listed)
+        .execute(&mut *trx)
+        .await
+        .map_err(Error::Sql)?;
+    }
+
+    Ok(totals)
+}
+
+pub(super) async fn profile_stashes(pool: &SqlitePool, profile_id: i64) -> Result<Vec<String>> {
+    let stashes = sqlx::query_as::<_, (String,)>(
+        "SELECT stash_id FROM profile_stash_assoc WHERE profile_id = ?",
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    Ok(stashes.into_iter().map(|s| s.0).collect())
+}
+
+/// Fetches a tab from the PoE API. Map tabs are fetched section by section and their
+/// items are recorded under the map tab itself.
+pub(super) async fn fetch_tab(
+    client: &PoeClient<'_>,
+    league: &str,
+    stash_id: &str,
+) -> Result<FetchedTab> {
+    let tab = client.stash(league, stash_id).await?;
+    let mut items = tab.items.unwrap_or_default();
+
+    if tab.r#type == "MapStash" {
+        for section in &tab.children {
+            let section = client.stash_section(league, &tab.id, &section.id).await?;
+            items.extend(section.items.unwrap_or_default());
+        }
+    }
+
+    Ok(FetchedTab {
+        stash_id: tab.id,
+        items,
+    })
+}
+
+/// Snapshots interrupted longer ago than this are discarded instead of kept for resuming,
+/// as finishing them would mix tabs from very different points in time.
+const RESUME_WINDOW_HOURS: i64 = 6;
+
+/// Shortest interval between scheduled snapshots of a profile. Every snapshot requests each
+/// of the profile's tabs from the stash API, which only allows a few dozen requests every
+/// few minutes, so shorter intervals would leave snapshots queued on the rate limiter.
+pub(super) const MIN_SNAPSHOT_INTERVAL_MINUTES: i64 = 10;
+
+/// Raises a profile's snapshot interval to the minimum, leaving 0 (off) as it is.
+pub(super) fn snapshot_interval(minutes: i64) -> i64 {
+    if minutes <= 0 {
+        0
+    } else {
+        minutes.max(MIN_SNAPSHOT_INTERVAL_MINUTES)
+    }
+}
+
+/// Starts an empty snapshot of `profile`, priced at the latest revision of its pricing
+/// league.
+async fn begin_snapshot(pool: &SqlitePool, profile: &Profile) -> Result<Snapshot> {
+    let revision =
+        pricing::latest_revision(pool, &profile.pricing_league, &profile.price_provider).await?;
+
+    sqlx::query_as::<_, Snapshot>(
+        "INSERT INTO snapshots (profile_id, timestamp, pricing_revision, value, status) VALUES (?, ?, ?, ?, ?) RETURNING *",
+    )
+    .bind(profile.id)
+    .bind(chrono::Local::now().naive_local())
+    .bind(revision.id)
+    .bind(0.0)
+    .bind(SnapshotStatus::Pending)
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+/// Writes the items of `tab` into `snapshot`, adds them to its totals and records the tab
+/// as fetched, all in one transaction.
+async fn write_tab(
+    pool: &SqlitePool,
+    snapshot: &Snapshot,
+    tab: &FetchedTab,
+    valuer: &Valuer,
+) -> Result<()> {
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+
+    let totals = insert_items(&mut trx, snapshot, &tab.stash_id, &tab.items, valuer).await?;
+
+    sqlx::query(
+        "UPDATE snapshots SET value = value + ?, low_confidence_value = low_confidence_value + ?, listed_value = listed_value + ? WHERE id = ?",
+    )
+    .bind(totals.value)
+    .bind(totals.low_confidence_value)
+    .bind(totals.listed_value)
+    .bind(snapshot.id)
+    .execute(&mut trx)
+    .await
+    .map_err(Error::Sql)?;
+
+    sqlx::query("INSERT INTO snapshot_tabs (snapshot_id, stash_id, fetched_at) VALUES (?, ?, ?)")
+        .bind(snapshot.id)
+        .bind(&tab.stash_id)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut trx)
+        .await
+        .map_err(Error::Sql)?;
+
+    trx.commit().await.map_err(Error::Sql)
+}
+
+async fn set_status(
+    pool: &SqlitePool,
+    snapshot_id: i64,
+    status: SnapshotStatus,
+    error: Option<String>,
+) -> Result<Snapshot> {
+    sqlx::query_as::<_, Snapshot>(
+        "UPDATE snapshots SET status = ?, error = ? WHERE id = ? RETURNING *",
+    )
+    .bind(status)
+    .bind(error)
+    .bind(snapshot_id)
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Sql)
+}
+
+/// Fetches and writes every tab of the profile that `snapshot` doesn't hold yet.
+async fn fill_snapshot(
+    pool: &SqlitePool,
+    prices: &PriceCache,
+    client: &PoeClient<'_>,
+    profile: &Profile,
+    snapshot: &Snapshot,
+) -> Result<()> {
+    let revision = pricing::revision(pool, snapshot.pricing_revision).await?;
+    let valuer = Valuer::load(pool, prices, &revision, ValuationSettings::from(profile)).await?;
+
+    let fetched =
+        sqlx::query_as::<_, (String,)>("SELECT stash_id FROM snapshot_tabs WHERE snapshot_id = ?")
+            .bind(snapshot.id)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Sql)?;
+
+    for stash_id in profile_stashes(pool, profile.id).await? {
+        if fetched.iter().any(|(f,)| *f == stash_id) {
+            continue;
+        }
+
+        let tab = fetch_tab(client, &profile.league_id, &stash_id).await?;
+        write_tab(pool, snapshot, &tab, &valuer).await?;
+    }
+
+    Ok(())
+}
+
+/// Fills `snapshot` and marks it complete, or failed if anything goes wrong. The tabs
+/// written until then are kept so that a failed snapshot can be resumed.
+async fn run_snapshot(
+    pool: &SqlitePool,
+    prices: &PriceCache,
+    client: &PoeClient<'_>,
+    profile: &Profile,
+    snapshot: Snapshot,
+) -> Result<Snapshot> {
+    match fill_snapshot(pool, prices, client, profile, &snapshot).await {
+        Ok(()) => set_status(pool, snapshot.id, SnapshotStatus::Complete, None).await,
+        Err(e) => {
+            set_status(
+                pool,
+                snapshot.id,
+                SnapshotStatus::Failed,
+                Some(e.to_string()),
+            )
+            .await?;
+            Err(e)
+        }
+    }
+}
+
+pub(super) async fn delete(
+    trx: &mut Transaction<'_, Sqlite>,
+    snapshot_id: i64,
+) -> Result<()> {
+    for query in [
+        "DELETE FROM valuations WHERE snapshot_id = ?",
+        "DELETE FROM item WHERE snapshot_id = ?",
+        "DELETE FROM snapshot_tabs WHERE snapshot_id = ?",
+        "DELETE FROM snapshots WHERE id = ?",
+    ] {
+        sqlx::query(query)
+            .bind(snapshot_id)
+            .execute(&mut *trx)
+            .await
+            .map_err(Error::Sql)?;
+    }
+
+    Ok(())
+}
+
+/// Cleans up after snapshots that were still running when the app was closed: they are
+/// marked failed so they can be resumed, or discarded if they are too old for that.
+pub(super) async fn recover(pool: &SqlitePool) -> Result<()> {
+    sqlx::query("UPDATE snapshots SET status = ?, error = ? WHERE status = ?")
+        .bind(SnapshotStatus::Failed)
+        .bind("Interrupted")
+        .bind(SnapshotStatus::Pending)
+        .execute(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    let cutoff = chrono::Local::now().naive_local() - Duration::hours(RESUME_WINDOW_HOURS);
+    let stale =
+        sqlx::query_as::<_, (i64,)>("SELECT id FROM snapshots WHERE status = ? AND timestamp < ?")
+            .bind(SnapshotStatus::Failed)
+            .bind(cutoff)
+            .fetch_all(pool)
+            .await
+            .map_err(Error::Sql)?;
+
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+    for (id,) in &stale {
+        delete(&mut trx, *id).await?;
+    }
+    trx.commit().await.map_err(Error::Sql)?;
+
+    Ok(())
+}
+
+/// Loads a snapshot to compare or revalue, which only makes sense once it holds every tab.
+pub(super) async fn load_complete(pool: &SqlitePool, snapshot_id: i64) -> Result<Snapshot> {
+    let snapshot = sqlx::query_as::<_, Snapshot>("SELECT * FROM snapshots WHERE id = ?")
+        .bind(snapshot_id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    if snapshot.status != SnapshotStatus::Complete {
+        return Err(Error::SnapshotIncomplete(snapshot.id));
+    }
+
+    Ok(snapshot)
+}
+
+async fn load_profile(pool: &SqlitePool, profile_id: i64) -> Result<Profile> {
+    sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
+        .bind(profile_id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)
+}
+
+/// Fetches every tab of a profile and writes them as a new snapshot.
+///
+/// The database is only locked to clone the pool, as fetching can take minutes when
+/// rate limited.
+pub(super) async fn take_snapshot(
+    con: &DbCon,
+    http: &HttpClient,
+    tokens: &Tokens,
+    limiter: &RateLimiter,
+    profile_id: i64,
+) -> Result<Snapshot> {
+    let pool = con
+        .db
+        .lock()
+        .await
+        .clone()
+        .ok_or(Error::DatabaseNotLoaded)?;
+
+    let profile = load_profile(&pool, profile_id).await?;
+    let client = PoeClient::new(http.client(), tokens, limiter)?;
+    let snapshot = begin_snapshot(&pool, &profile).await?;
+
+    run_snapshot(&pool, &con.prices, &client, &profile, snapshot).await
+}
+
+/// Like [`take_snapshot`], but for snapshots taken on a schedule. If the profile's last
+/// scheduled run failed its snapshot is resumed instead, so a run of failures leaves at most
+/// one partial snapshot behind. Failed snapshots too old to resume are discarded.
+pub(super) async fn take_scheduled_snapshot(
+    con: &DbCon,
+    http: &HttpClient,
+    tokens: &Tokens,
+    limiter: &RateLimiter,
+    profile_id: i64,
+) -> Result<Snapshot> {
+    let pool = con
+        .db
+        .lock()
+        .await
+        .clone()
+        .ok_or(Error::DatabaseNotLoaded)?;
+
+    let profile = load_profile(&pool, profile_id).await?;
+    let client = PoeClient::new(http.client(), tokens, limiter)?;
+
+    let failed = sqlx::query_as::<_, Snapshot>(
+        "SELECT * FROM snapshots WHERE profile_id = ? AND status = ? ORDER BY timestamp DESC",
+    )
+    .bind(profile.id)
+    .bind(SnapshotStatus::Failed)
+    .fetch_all(&pool)
+    .await
+    .map_err(Error::Sql)?;
+
+    let cutoff = chrono::Local::now().naive_local() - Duration::hours(RESUME_WINDOW_HOURS);
+    let (resumable, stale): (Vec<_>, Vec<_>) =
+        failed.into_iter().partition(|s| s.timestamp >= cutoff);
+
+    let mut trx = pool.begin().await.map_err(Error::Sql)?;
+    for snapshot in &stale {
+        delete(&mut trx, snapshot.id).await?;
+    }
+    trx.commit().await.map_err(Error::Sql)?;
+
+    let snapshot = match resumable.first() {
+        Some(failed) => set_status(&pool, failed.id, SnapshotStatus::Pending, None).await?,
+        None => begin_snapshot(&pool, &profile).await?,
+    };
+
+    run_snapshot(&pool, &con.prices, &client, &profile, snapshot).await
+}
+
+#[tauri::command]
+pub(super) async fn snapshot_profile(
+    con: State<'_, DbCon>,
+    http: State<'_, HttpClient>,
+    tokens: State<'_, Tokens>,
+    limiter: State<'_, RateLimiter>,
+    profile_id: i64,
+) -> Result<Snapshot> {
+    take_snapshot(&con, &http, &tokens, &limiter, profile_id).await
+}
+
+/// Fetches the tabs a failed snapshot is missing and completes it.
+#[tauri::command]
+pub(super) async fn resume_snapshot(
+    con: State<'_, DbCon>,
+    http: State<'_, HttpClient>,
+    tokens: State<'_, Tokens>,
+    limiter: State<'_, RateLimiter>,
+    snapshot_id: i64,
+) -> Result<Snapshot> {
+    let pool = con
+        .db
+        .lock()
+        .await
+        .clone()
+        .ok_or(Error::DatabaseNotLoaded)?;
+
+    let snapshot = sqlx::query_as::<_, Snapshot>("SELECT * FROM snapshots WHERE id = ?")
+        .bind(snapshot_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(Error::Sql)?;
+    if snapshot.status != SnapshotStatus::Failed {
+        return Err(Error::SnapshotNotResumable(snapshot.id));
+    }
+
+    let profile = load_profile(&pool, snapshot.profile_id).await?;
+    let client = PoeClient::new(http.client(), &tokens, &limiter)?;
+    let snapshot = set_status(&pool, snapshot.id, SnapshotStatus::Pending, None).await?;
+
+    run_snapshot(&pool, &con.prices, &client, &profile, snapshot).await
+}
+
+/// Snapshots of a profile that failed and can still be resumed or deleted.
+#[tauri::command]
+pub(super) async fn list_failed_snapshots(
+    con: State<'_, DbCon>,
+    profile_id: i64,
+) -> Result<Vec<Snapshot>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, Snapshot>("SELECT * FROM snapshots WHERE profile_id = ? AND status = ?")
+        .bind(profile_id)
+        .bind(SnapshotStatus::Failed)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)
+}
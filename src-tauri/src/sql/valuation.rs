@@ -0,0 +1,147 @@
+use super::model::{
+    ItemRow, ItemWithPrice, Profile, Snapshot, SnapshotValuation, Valuation, ValuationMode,
+};
+use super::pricing::{ValuationSettings, Valuer};
+use super::{pricing, snapshots, DbCon, Error, Result};
+use sqlx::sqlite::SqlitePool;
+use tauri::State;
+
+pub(super) struct SnapshotValue {
+    pub value: f64,
+    pub low_confidence_value: f64,
+    pub listed_value: f64,
+    pub items: Vec<ItemWithPrice>,
+}
+
+/// Prices every item of `snapshot` again without touching the stored values.
+pub(super) async fn value_snapshot(
+    pool: &SqlitePool,
+    snapshot: &Snapshot,
+    valuer: &Valuer,
+) -> Result<SnapshotValue> {
+    let settings = &valuer.settings;
+    let item_rows = sqlx::query_as::<_, ItemRow>("SELECT * FROM item WHERE snapshot_id = ?")
+        .bind(snapshot.id)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    let mut total = SnapshotValue {
+        value: 0.0,
+        low_confidence_value: 0.0,
+        listed_value: 0.0,
+        items: Vec::with_capacity(item_rows.len()),
+    };
+
+    for row in item_rows {
+        let item = row.data.0;
+        let value = valuer.value(&item);
+
+        if value.excluded(settings) {
+            total.low_confidence_value += value.value;
+        } else {
+            total.value += value.value;
+        }
+        total.listed_value += value.listed_or_market(settings);
+
+        total.items.push(ItemWithPrice {
+            item,
+            price: value.value,
+            change: value.change,
+            low_confidence: value.low_confidence,
+            listed_price: value.listed,
+        });
+    }
+
+    Ok(total)
+}
+
+#[tauri::command]
+pub(super) async fn revalue_snapshot(
+    con: State<'_, DbCon>,
+    snapshot_id: i64,
+    pricing_revision: Option<i64>,
+    valuation_mode: Option<ValuationMode>,
+    save: bool,
+) -> Result<SnapshotValuation> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    let snapshot = snapshots::load_complete(pool, snapshot_id).await?;
+
+    let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
+        .bind(snapshot.profile_id)
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sql)?;
+
+    // The profile may have moved to another league or provider since the snapshot was taken,
+    // so revisions are checked against the one the snapshot was priced at
+    let original = pricing::revision(pool, snapshot.pricing_revision).await?;
+    let revision = match pricing_revision {
+        Some(id) => pricing::revision(pool, id).await?,
+        None => pricing::latest_revision(pool, &original.league, &original.provider).await?,
+    };
+
+    if revision.league != original.league {
+        return Err(Error::RevisionLeagueMismatch(revision.id, original.league));
+    }
+    if revision.provider != original.provider {
+        return Err(Error::RevisionProviderMismatch(
+            revision.id,
+            original.provider,
+        ));
+    }
+
+    let mut settings = ValuationSettings::from(&profile);
+    settings.mode = valuation_mode.unwrap_or(settings.mode);
+    let valuer = Valuer::load(pool, &con.prices, &revision, settings).await?;
+    let total = value_snapshot(pool, &snapshot, &valuer).await?;
+
+    let saved = if save {
+        Some(
+            sqlx::query_as::<_, Valuation>(
+                "INSERT INTO valuations (snapshot_id, pricing_revision, timestamp, value, valuation_mode, low_confidence_value, listed_value) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
+            )
+            .bind(snapshot.id)
+            .bind(revision.id)
+            .bind(chrono::Local::now().naive_local())
+            .bind(total.value)
+            .bind(settings.mode)
+            .bind(total.low_confidence_value)
+            .bind(total.listed_value)
+            .fetch_one(pool)
+            .await
+            .map_err(Error::Sql)?,
+        )
+    } else {
+        None
+    };
+
+    Ok(SnapshotValuation {
+        snapshot_id: snapshot.id,
+        pricing_revision: revision.id,
+        valuation_mode: settings.mode,
+        original_value: snapshot.value,
+        value: total.value,
+        low_confidence_value: total.low_confidence_value,
+        listed_value: total.listed_value,
+        saved,
+        items: total.items,
+    })
+}
+
+#[tauri::command]
+pub(super) async fn list_valuations(
+    con: State<'_, DbCon>,
+    snapshot_id: i64,
+) -> Result<Vec<Valuation>> {
+    let mutex = con.db.lock().await;
+    let pool = mutex.as_ref().ok_or(Error::DatabaseNotLoaded)?;
+
+    sqlx::query_as::<_, Valuation>("SELECT * FROM valuations WHERE snapshot_id = ? ORDER BY id")
+        .bind(snapshot_id)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sql)
+}
@@ -5,6 +5,7 @@ import TopBar from './components/TopBar';
 import { useState, useEffect } from 'react';
 import { Snapshot } from './bindings';
 import { invoke } from '@tauri-apps/api';
+import { refreshLeagues } from './api/db';
 
 function App() {
 	const [snapshot, setSnapshot] = useState<Snapshot>({} as unknown as Snapshot);
@@ -12,20 +13,15 @@ function App() {
 	const [total, setTotal] = useState(0);
 	const [selectedProfileId, setSelectedProfileId] = useState<number | bigint | null>(null);
 
-	const MINUTE_MS = 60000;
-
 	useEffect(() => {
-		invoke('plugin:sql|has_recent_prices').then((x) => {
-			if (!(x as boolean)) {
-				invoke('plugin:sql|fetch_prices');
-			}
-		});
-
-		const interval = setInterval(() => {
-			invoke('plugin:sql|fetch_prices');
-		}, MINUTE_MS * 60);
-
-		return () => clearInterval(interval);
+		refreshLeagues(localStorage.getItem('oauth_token'))
+			.catch(console.log)
+			.then(() => invoke('plugin:sql|has_recent_prices'))
+			.then((x) => {
+				if (!(x as boolean)) {
+					invoke('plugin:sql|fetch_prices');
+				}
+			});
 	}, []);
 
 	return (
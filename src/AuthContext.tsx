@@ -26,7 +26,9 @@ export function OAuthProvider({ children }: { children: React.ReactNode }) {
 	const [token, setToken] = useState('');
 
 	useEffect(() => {
-		setToken(localStorage.getItem('oauth_token') || '');
+		const stored = localStorage.getItem('oauth_token');
+		setToken(stored || '');
+		invoke('plugin:oauth|set_token', { accessToken: stored });
 	}, []);
 
 	useEffect(() => {
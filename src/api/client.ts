@@ -1,7 +1,6 @@
 import axios from 'axios';
 import axiosTauriApiAdapter from 'axios-tauri-api-adapter';
-import { RateLimiter } from 'limiter';
-import { ExtendedStashTab } from '../types/types';
+import { invoke } from '@tauri-apps/api';
 
 const client = axios.create({
 	adapter: axiosTauriApiAdapter,
@@ -27,63 +26,11 @@ client.interceptors.response.use(
 			localStorage.removeItem('oauth_token');
 			localStorage.removeItem('refresh_token');
 			localStorage.removeItem('valid_until');
+			invoke('plugin:oauth|set_token', { accessToken: null });
 			window.location.href = '/';
 		}
 		return error;
 	}
 );
 
-const limiters: Record<string, RateLimiter[]> = {};
-
-export async function fetch_stash(id: string, league: string): Promise<ExtendedStashTab> {
-	return (await fetch_stashes([id], league))[0];
-}
-
-export async function fetch_stashes(ids: string[], league: string): Promise<ExtendedStashTab[]> {
-	const policy = 'stash-request-limit';
-
-	const tabs: ExtendedStashTab[] = [];
-
-	async function fetch(id: string): Promise<ExtendedStashTab> {
-		for (const l of limiters[policy]) {
-			await l.removeTokens(1);
-		}
-		const {
-			data: { stash },
-		} = await client.get<{
-			stash: ExtendedStashTab;
-		}>(`stash/${league}/${id}`);
-		return stash;
-	}
-
-	if (!(policy in limiters)) {
-		// first stash tab to be fetched so we have to send one request and extract some info on the rate limits
-		const first = ids.pop();
-		const {
-			headers,
-			data: { stash },
-		} = await client.get<{ stash: ExtendedStashTab }>(`stash/${league}/${first}`);
-		tabs.push(stash);
-		const rateLimitString: string = headers['x-rate-limit-account'];
-		const limits = rateLimitString.split(',').map((x) => {
-			const y = x.split(':');
-			return y.map((z) => parseInt(z));
-		});
-		const rateLimiters = [];
-		for (const l of limits) {
-			const rl = new RateLimiter({ tokensPerInterval: l[0] - 1, interval: l[1] * 1000 });
-			await rl.removeTokens(1);
-			rateLimiters.push(rl);
-		}
-		limiters[policy] = rateLimiters;
-	}
-
-	for (const id of ids) {
-		const data = await fetch(id);
-		tabs.push(data);
-	}
-
-	return tabs;
-}
-
 export default client;
@@ -1,5 +1,23 @@
 import { invoke } from '@tauri-apps/api';
-import { Stash, Profile, ProfileWithStashes, Snapshot, Item, UseEffectResponse } from '../bindings';
+import {
+	Stash,
+	Profile,
+	ProfileWithStashes,
+	Snapshot,
+	UseEffectResponse,
+	League,
+	Price,
+	PriceRevision,
+	SnapshotValuation,
+	Valuation,
+	ValuationMode,
+	PriceOverride,
+	Settings,
+	PruneReport,
+	PriceAnomaly,
+	SnapshotDiff,
+	ValueAttribution,
+} from '../bindings';
 
 export * from '../bindings';
 
@@ -25,20 +43,16 @@ export async function getProfiles(): Promise<ProfileWithStashes[]> {
 	return await invoke('plugin:sql|get_profiles');
 }
 
-export async function newSnapshot(profileId: number): Promise<Snapshot> {
-	return await invoke('plugin:sql|new_snapshot', { profileId });
+export async function snapshotProfile(profileId: number): Promise<Snapshot> {
+	return await invoke('plugin:sql|snapshot_profile', { profileId });
 }
 
-export async function addItemsToSnapshot(
-	snapshot: Snapshot,
-	items: Item[],
-	stashId: string
-): Promise<number> {
-	return await invoke('plugin:sql|add_items_to_snapshot', { snapshot, items, stashId });
+export async function resumeSnapshot(snapshotId: number): Promise<Snapshot> {
+	return await invoke('plugin:sql|resume_snapshot', { snapshotId });
 }
 
-export async function snapshotSetValue(snapshot: Snapshot, value: number) {
-	await invoke('plugin:sql|snapshot_set_value', { snapshot, value });
+export async function listFailedSnapshots(profileId: number): Promise<Snapshot[]> {
+	return await invoke('plugin:sql|list_failed_snapshots', { profileId });
 }
 
 export async function fetchPrices(): Promise<PricingRevision> {
@@ -72,6 +86,109 @@ export async function basicallyThisUseEffect(snapshot: Snapshot): Promise<UseEff
 	return await invoke('plugin:sql|basically_this_use_effect', { snapshot });
 }
 
+export async function diffSnapshots(a: number, b: number): Promise<SnapshotDiff> {
+	return await invoke('plugin:sql|diff_snapshots', { a, b });
+}
+
+export async function attributeValueChange(a: number, b: number): Promise<ValueAttribution> {
+	return await invoke('plugin:sql|attribute_value_change', { a, b });
+}
+
 export async function getPricingLeagues(): Promise<string[]> {
 	return await invoke('plugin:sql|get_pricing_leagues');
 }
+
+export async function refreshLeagues(token: string | null): Promise<League[]> {
+	return await invoke('plugin:sql|refresh_leagues', { token });
+}
+
+export async function getLeagues(): Promise<League[]> {
+	return await invoke('plugin:sql|get_leagues');
+}
+
+export async function getPriceProviders(): Promise<string[]> {
+	return await invoke('plugin:sql|get_price_providers');
+}
+
+export async function getPriceHistory(
+	name: string,
+	league: string,
+	provider?: string
+): Promise<Price[]> {
+	return await invoke('plugin:sql|get_price_history', { name, league, provider });
+}
+
+export async function getPriceRevisions(league: string, provider?: string): Promise<PriceRevision[]> {
+	return await invoke('plugin:sql|get_price_revisions', { league, provider });
+}
+
+export async function getPriceAnomalies(revision: number): Promise<PriceAnomaly[]> {
+	return await invoke('plugin:sql|get_price_anomalies', { revision });
+}
+
+export async function revalueSnapshot(
+	snapshotId: number,
+	pricingRevision: number | null,
+	valuationMode: ValuationMode | null,
+	save: boolean
+): Promise<SnapshotValuation> {
+	return await invoke('plugin:sql|revalue_snapshot', {
+		snapshotId,
+		pricingRevision,
+		valuationMode,
+		save,
+	});
+}
+
+export async function listValuations(snapshotId: number): Promise<Valuation[]> {
+	return await invoke('plugin:sql|list_valuations', { snapshotId });
+}
+
+export async function getPriceOverrides(profileId: number | null): Promise<PriceOverride[]> {
+	return await invoke('plugin:sql|get_price_overrides', { profileId });
+}
+
+export async function createPriceOverride(
+	profileId: number | null,
+	name: string,
+	baseType: string | null,
+	variant: string | null,
+	price: number
+): Promise<PriceOverride> {
+	return await invoke('plugin:sql|create_price_override', {
+		profileId,
+		name,
+		baseType,
+		variant,
+		price,
+	});
+}
+
+export async function updatePriceOverride(priceOverride: PriceOverride): Promise<PriceOverride> {
+	return await invoke('plugin:sql|update_price_override', { priceOverride });
+}
+
+export async function deletePriceOverride(id: number): Promise<void> {
+	return await invoke('plugin:sql|delete_price_override', { id });
+}
+
+export async function getSettings(): Promise<Settings> {
+	return await invoke('plugin:sql|get_settings');
+}
+
+export async function updateSettings(settings: Settings): Promise<Settings> {
+	return await invoke('plugin:sql|update_settings', { settings });
+}
+
+export async function importPrices(
+	paths: string[],
+	league: string,
+	provider?: string,
+	category?: string
+): Promise<PriceRevision> {
+	return await invoke('plugin:sql|import_prices', { paths, league, provider, category });
+}
+
+export async function prunePriceHistory(): Promise<PruneReport> {
+	return await invoke('plugin:sql|prune_price_history');
+}
@@ -1,3 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ItemProperty } from "./ItemProperty";
+import type { ItemSocket } from "./ItemSocket";
 
-export interface Item { verified: boolean, w: bigint, h: bigint, icon: string, support: boolean | null, stackSize: bigint | null, maxStackSize: bigint | null, stackSizeText: string | null, league: string | null, id: string | null, fractured: boolean | null, synthesized: boolean | null, name: string, typeLine: string, baseType: string, identified: boolean, itemLevel: bigint | null, frameType: bigint, }
\ No newline at end of file
+export interface Item { verified: boolean, w: bigint, h: bigint, icon: string, support: boolean | null, stackSize: bigint | null, maxStackSize: bigint | null, stackSizeText: string | null, league: string | null, id: string | null, fractured: boolean | null, synthesized: boolean | null, name: string, typeLine: string, baseType: string, identified: boolean, itemLevel: bigint | null, frameType: bigint, corrupted: boolean | null, sockets: Array<ItemSocket> | null, properties: Array<ItemProperty> | null, influences: Record<string, boolean> | null, enchantMods: Array<string> | null, note: string | null, }
\ No newline at end of file
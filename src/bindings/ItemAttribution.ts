@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface ItemAttribution { stash_id: string, category: string, name: string, quantity_before: bigint, quantity_after: bigint, unit_price_before: number, unit_price_after: number, quantity_effect: number, price_effect: number, total: number, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Item } from "./Item";
+
+export interface ItemChange { stash_id: string, category: string, name: string, item: Item, quantity_before: bigint, quantity_after: bigint, chaos_delta: number, }
\ No newline at end of file
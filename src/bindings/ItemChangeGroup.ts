@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface ItemChangeGroup { stash_id: string, category: string, added: bigint, removed: bigint, changed: bigint, chaos_delta: number, }
\ No newline at end of file
@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Item } from "./Item";
 
-export interface ItemWithPrice { item: Item, price: number, }
\ No newline at end of file
+export interface ItemWithPrice { item: Item, price: number, change: number | null, low_confidence: boolean, listed_price: number | null, }
\ No newline at end of file
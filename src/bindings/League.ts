@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface League { id: string, realm: string | null, start_at: string | null, end_at: string | null, ninja_indexed: boolean, updated_at: string, }
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface Price { id: bigint, name: string, price: number, revision: bigint, fully_linked: boolean, timestamp: string, }
\ No newline at end of file
+export interface Price { id: bigint, name: string, price: number, revision: bigint, fully_linked: boolean, timestamp: string, league: string, provider: string, category: string | null, base_type: string | null, variant: string | null, links: bigint | null, gem_level: bigint | null, gem_quality: bigint | null, gem_quality_type: string | null, corrupted: boolean | null, map_tier: bigint | null, blight: string | null, item_level: bigint | null, influence: string | null, passives: bigint | null, sparkline: Array<number | null> | null, total_change: number | null, pay_price: number | null, listing_count: bigint | null, }
\ No newline at end of file
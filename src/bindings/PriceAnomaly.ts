@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface PriceAnomaly { id: bigint, revision: bigint, previous_revision: bigint, price_id: bigint, name: string, category: string | null, variant: string | null, price: number, previous_price: number, previous_pay_price: number | null, factor: number, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface PriceFetchProgress { league: string, provider: string, category: string, done: bigint, total: bigint, }
\ No newline at end of file
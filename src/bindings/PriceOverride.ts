@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface PriceOverride { id: bigint, profile_id: bigint | null, name: string, base_type: string | null, variant: string | null, price: number, timestamp: string, }
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ValuationMode } from "./ValuationMode";
 
-export interface Profile { id: bigint, name: string, league_id: string, pricing_league: string, }
\ No newline at end of file
+export interface Profile { id: bigint, name: string, league_id: string, pricing_league: string, price_provider: string, valuation_mode: ValuationMode, min_listing_count: bigint, exclude_low_confidence: boolean, use_previous_on_anomaly: boolean, snapshot_interval_minutes: bigint, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface Settings { price_refresh_minutes: bigint, http_timeout_seconds: bigint, price_retention_days: bigint, prune_prices_automatically: boolean, anomaly_factor: number, }
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SnapshotStatus } from "./SnapshotStatus";
 
-export interface Snapshot { id: bigint, profile_id: bigint, timestamp: string, pricing_revision: bigint, value: number, }
\ No newline at end of file
+export interface Snapshot { id: bigint, profile_id: bigint, timestamp: string, pricing_revision: bigint, value: number, low_confidence_value: number, listed_value: number, status: SnapshotStatus, error: string | null, }
\ No newline at end of file
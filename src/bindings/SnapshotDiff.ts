@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ItemChange } from "./ItemChange";
+import type { ItemChangeGroup } from "./ItemChangeGroup";
+
+export interface SnapshotDiff { from_snapshot: bigint, to_snapshot: bigint, chaos_delta: number, added: Array<ItemChange>, removed: Array<ItemChange>, changed: Array<ItemChange>, groups: Array<ItemChangeGroup>, }
\ No newline at end of file
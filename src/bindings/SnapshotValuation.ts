@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ItemWithPrice } from "./ItemWithPrice";
+import type { Valuation } from "./Valuation";
+import type { ValuationMode } from "./ValuationMode";
+
+export interface SnapshotValuation { snapshot_id: bigint, pricing_revision: bigint, valuation_mode: ValuationMode, original_value: number, value: number, low_confidence_value: number, listed_value: number, saved: Valuation | null, items: Array<ItemWithPrice>, }
\ No newline at end of file
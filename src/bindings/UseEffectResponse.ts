@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ItemWithPrice } from "./ItemWithPrice";
 
-export interface UseEffectResponse { items: Array<ItemWithPrice>, total_chaos: number, total_div: number, }
\ No newline at end of file
+export interface UseEffectResponse { items: Array<ItemWithPrice>, total_chaos: number, total_div: number, low_confidence_chaos: number, listed_chaos: number, }
\ No newline at end of file
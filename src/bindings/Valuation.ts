@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ValuationMode } from "./ValuationMode";
+
+export interface Valuation { id: bigint, snapshot_id: bigint, pricing_revision: bigint, timestamp: string, value: number, valuation_mode: ValuationMode, low_confidence_value: number, listed_value: number, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ItemAttribution } from "./ItemAttribution";
+
+export interface ValueAttribution { from_snapshot: bigint, to_snapshot: bigint, from_revision: bigint, to_revision: bigint, value_before: number, value_after: number, quantity_effect: number, price_effect: number, appeared: number, disappeared: number, items: Array<ItemAttribution>, }
\ No newline at end of file
@@ -2,12 +2,34 @@
 // Do not edit it manually.
 
 export * from "./Item"
+export * from "./ItemAttribution"
+export * from "./ItemChange"
+export * from "./ItemChangeGroup"
 export * from "./ItemProperty"
+export * from "./ItemSocket"
 export * from "./ItemWithPrice"
+export * from "./League"
 export * from "./Price"
+export * from "./PriceAnomaly"
+export * from "./PriceFetchFailed"
+export * from "./PriceFetchProgress"
+export * from "./PriceOverride"
+export * from "./PriceRevision"
+export * from "./PricesUpdated"
 export * from "./Profile"
 export * from "./ProfileStashAssoc"
 export * from "./ProfileWithStashes"
+export * from "./PruneFailed"
+export * from "./PruneReport"
+export * from "./ScheduledSnapshotFailed"
+export * from "./ScheduledSnapshotStarted"
+export * from "./Settings"
 export * from "./Snapshot"
+export * from "./SnapshotDiff"
+export * from "./SnapshotStatus"
+export * from "./SnapshotValuation"
 export * from "./Stash"
-export * from "./UseEffectResponse"
\ No newline at end of file
+export * from "./UseEffectResponse"
+export * from "./Valuation"
+export * from "./ValuationMode"
+export * from "./ValueAttribution"
\ No newline at end of file
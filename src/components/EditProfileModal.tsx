@@ -54,9 +54,11 @@ const EditProfileModal = ({ isOpen, onClose, profileData }: Props) => {
 	}, [profileData]);
 
 	const handleFormSubmit = async (values: CreateProfilePayload) => {
+		if (!profileData) return;
+		// Settings the form doesn't edit are sent back unchanged
 		const remappedValues = {
 			profile: {
-				id: profileData?.profile.id as bigint,
+				...profileData.profile,
 				name: values.profileName,
 				pricing_league: values.pricingLeague,
 				league_id: values.leagueId,
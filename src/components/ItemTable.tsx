@@ -16,6 +16,8 @@ interface ItemRecord {
 	name: string;
 	amount: number;
 	value: number;
+	change: number | null;
+	listed: number | null;
 	icon: string;
 }
 
@@ -50,6 +52,8 @@ const ItemTable = ({ snapshot, setTotal, isSnapshotLoading }: Props) => {
 					name: item.name ? `${item.name} ${item.typeLine}` : item.typeLine,
 					amount: amount,
 					value: value,
+					change: item_data.change,
+					listed: item_data.listed_price,
 					icon: item.icon,
 				});
 			}
@@ -104,6 +108,24 @@ const ItemTable = ({ snapshot, setTotal, isSnapshotLoading }: Props) => {
 								minimumFractionDigits: 2,
 							}),
 					},
+					{
+						accessor: 'change',
+						title: '7d',
+						sortable: true,
+						render: ({ change }) =>
+							change === null ? '' : `${change > 0 ? '+' : ''}${change.toFixed(0)}%`,
+					},
+					{
+						accessor: 'listed',
+						sortable: true,
+						render: ({ listed }) =>
+							listed === null
+								? ''
+								: listed.toLocaleString(undefined, {
+										maximumFractionDigits: 2,
+										minimumFractionDigits: 2,
+								  }),
+					},
 				]}
 				sortStatus={sortStatus}
 				onSortStatusChange={setSortStatus}
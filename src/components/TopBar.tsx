@@ -5,18 +5,17 @@ import {
 	Flex,
 	Paper,
 	Select,
+	Text,
 	Title,
 	createStyles,
 } from '@mantine/core';
 import { useDisclosure } from '@mantine/hooks';
 import { IconBell, IconPlus, IconSettings, IconTrash } from '@tabler/icons-react';
 import { useQueryClient } from '@tanstack/react-query';
-import { invoke } from '@tauri-apps/api';
-import { useEffect } from 'react';
-import { fetch_stashes } from '../api/client';
+import { useEffect, useState } from 'react';
+import { snapshotProfile } from '../api/db';
 import { Snapshot } from '../bindings';
 import { useGetProfiles, useGetSnapshots } from '../services/services';
-import { Item } from '../types/types';
 import EditProfileModal from './EditProfileModal';
 import ProfileModal from './ProfileModal';
 
@@ -35,6 +34,7 @@ const TopBar = ({
 }: Props) => {
 	const queryClient = useQueryClient();
 	const { classes } = useStyles();
+	const [snapshotError, setSnapshotError] = useState<string | null>(null);
 
 	const [isAddProfileModalOpen, { open: openAddProfileModal, close: closeAddProfileModal }] =
 		useDisclosure(false);
@@ -58,57 +58,17 @@ const TopBar = ({
 
 	const handleSnapshotButton = async () => {
 		setIsSnapshotLoading(true);
-		const snapshot: Snapshot = await invoke('plugin:sql|new_snapshot', {
-			profileId: selectedProfileId,
-		});
-
-		queryClient.invalidateQueries(['snapshots', selectedProfileId]);
-
-		const s = await fetch_stashes(
-			profilesData.find((x) => x.profile.id === selectedProfileId)?.stashes as string[],
-			profilesData.find((x) => x.profile.id === selectedProfileId)?.profile.league_id as string
-		);
-
-		const extraItems: Item[] = [];
-
-		let total = 0;
-
-		for (const stashtab of s) {
-			if (stashtab.type == 'MapStash') {
-				for (const child of stashtab.children) {
-					if (!child.metadata.map) return;
-					const item: any = {
-						verified: false,
-						w: 1,
-						h: 1,
-						icon: child.metadata?.map?.image,
-						name: child.metadata?.map?.name,
-						typeLine: child.metadata?.map?.name,
-						baseType: child.metadata?.map?.name,
-						identified: true,
-						frameType: 0,
-					};
-					await invoke('plugin:sql|add_items_to_snapshot', {
-						snapshot: snapshot,
-						items: [item],
-						stashId: stashtab.id,
-						league: stashtab,
-					});
-					extraItems.push(item);
-				}
-				setIsSnapshotLoading(false);
-			} else {
-				total = await invoke('plugin:sql|add_items_to_snapshot', {
-					snapshot: snapshot,
-					items: stashtab.items,
-					stashId: stashtab.id,
-				});
-				setIsSnapshotLoading(false);
-			}
+		setSnapshotError(null);
+		try {
+			const snapshot = await snapshotProfile(Number(selectedProfileId));
+			setSnapshot(snapshot);
+		} catch (e) {
+			// Commands reject with the error message, e.g. when the login has expired
+			setSnapshotError(String(e));
+		} finally {
+			setIsSnapshotLoading(false);
+			queryClient.invalidateQueries(['snapshots', selectedProfileId]);
 		}
-		snapshot.value = total;
-		setSnapshot(snapshot);
-		queryClient.invalidateQueries(['snapshots', selectedProfileId]);
 	};
 
 	return (
@@ -158,6 +118,11 @@ const TopBar = ({
 							<IconTrash size="16px" />
 						</ActionIcon>
 						<Divider orientation="vertical" />
+						{snapshotError && (
+							<Text color="red" size="sm">
+								{snapshotError}
+							</Text>
+						)}
 						<Button onClick={handleSnapshotButton} disabled={!selectedProfileId}>
 							Take Snapshot
 						</Button>
@@ -10,6 +10,7 @@ import {
 	listSnapshots,
 	updateProfile,
 	getPricingLeagues,
+	snapshotProfile,
 } from '../api/db';
 import { CreateProfilePayload } from '../components/ProfileModal';
 
@@ -61,7 +62,7 @@ export const useAddSnapshot = () => {
 	const queryClient = useQueryClient();
 
 	return useMutation<Snapshot, unknown, number | bigint | null>(
-		(profileId) => invoke('plugin:sql|new_snapshot', { profileId }),
+		(profileId) => snapshotProfile(Number(profileId)),
 		{
 			onSuccess: (data: Snapshot) =>
 				queryClient.invalidateQueries([QueryKeys.SNAPSHOTS, data.profile_id]),